@@ -1,14 +1,28 @@
 use std::collections::BinaryHeap;
+use std::convert::Infallible;
+
+use crate::error::ParseError;
+use crate::solution::Puzzle;
 
 type Food = Vec<u32>;
 
 #[aoc_generator(day1)]
-pub fn parse_input(input: &str) -> Vec<Food> {
-    input.split("\n\n").map(parse_elf_inv).collect()
+pub fn parse_input(input: &str) -> Result<Vec<Food>, ParseError> {
+    input
+        .split("\n\n")
+        .map(|elf| parse_elf_inv(input, elf))
+        .collect()
 }
 
-fn parse_elf_inv(elf: &str) -> Food {
-    elf.lines().flat_map(|s| s.parse::<u32>()).collect()
+fn parse_elf_inv(input: &str, elf: &str) -> Result<Food, ParseError> {
+    elf.lines()
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| {
+            s.trim()
+                .parse::<u32>()
+                .map_err(|_| ParseError::locate(input, s, "a calorie count"))
+        })
+        .collect()
 }
 
 #[aoc(day1, part1, iter)]
@@ -41,6 +55,29 @@ fn sum_top_n_heap(elves: &[Food], n: usize) -> u32 {
     heap.into_iter_sorted().take(n).sum()
 }
 
+pub struct Day01;
+
+impl Puzzle for Day01 {
+    const DAY: u32 = 1;
+
+    type Input = Vec<Food>;
+    type Output1 = u32;
+    type Output2 = u32;
+    type Error = Infallible;
+
+    fn parse(&self, input: &str) -> Result<Self::Input, ParseError> {
+        parse_input(input)
+    }
+
+    fn part1(&self, elves: &Self::Input) -> Result<u32, Infallible> {
+        Ok(solve_d01_pt1_heap(elves))
+    }
+
+    fn part2(&self, elves: &Self::Input) -> Result<u32, Infallible> {
+        Ok(solve_d01_pt2_heap(elves))
+    }
+}
+
 #[cfg(test)]
 mod tests {
 
@@ -64,7 +101,7 @@ mod tests {
 
     #[test]
     fn example_pt1() {
-        let input = parse_input(EXAMPLE_INPUT);
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
         let expect = 24000;
         let actual = solve_d01_pt1(&input);
         assert_eq!(expect, actual);
@@ -74,7 +111,7 @@ mod tests {
 
     #[test]
     fn solve_pt1() {
-        let input = parse_input(FILE_INPUT);
+        let input = parse_input(FILE_INPUT).unwrap();
         let expect = 69912;
         let actual = solve_d01_pt1(&input);
         assert_eq!(expect, actual);
@@ -84,7 +121,7 @@ mod tests {
 
     #[test]
     fn example_pt2() {
-        let input = parse_input(EXAMPLE_INPUT);
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
         let expect = 45000;
         let actual = solve_d01_pt2(&input);
         assert_eq!(expect, actual);
@@ -94,11 +131,17 @@ mod tests {
 
     #[test]
     fn solve_pt2() {
-        let input = parse_input(FILE_INPUT);
+        let input = parse_input(FILE_INPUT).unwrap();
         let expect = 208180;
         let actual = solve_d01_pt2(&input);
         assert_eq!(expect, actual);
         let actual = solve_d01_pt2_heap(&input);
         assert_eq!(expect, actual);
     }
+
+    #[test]
+    fn bad_calorie_count() {
+        let err = parse_input("1000\n\n20x0\n3000").unwrap_err();
+        assert_eq!(ParseError::new(3, 1, "20x0", "a calorie count"), err);
+    }
 }
@@ -1,3 +1,8 @@
+use std::convert::Infallible;
+
+use crate::error::ParseError;
+use crate::solution::Puzzle;
+
 static WIN_PTS: u32 = 6;
 static DRAW_PTS: u32 = 3;
 static LOSE_PTS: u32 = 0;
@@ -6,52 +11,69 @@ static PAPER_PTS: u32 = 2;
 static SCISS_PTS: u32 = 3;
 
 #[aoc_generator(day2, part1)]
-fn input_generator_pt1(input: &str) -> Vec<Round> {
-    input.trim().split('\n').map(parse_round_pt1).collect()
+fn input_generator_pt1(input: &str) -> Result<Vec<Round>, ParseError> {
+    input
+        .trim()
+        .lines()
+        .map(|l| parse_round_pt1(input, l))
+        .collect()
 }
 
-fn parse_round_pt1(s: &str) -> Round {
-    let plays = s.split(' ').collect::<Vec<&str>>();
-    (parse_play_pt1(plays[0]), parse_play_pt1(plays[1]))
+fn parse_round_pt1(input: &str, s: &str) -> Result<Round, ParseError> {
+    let (opp, own) = split_round(input, s)?;
+    Ok((parse_play_pt1(input, opp)?, parse_play_pt1(input, own)?))
 }
 
-fn parse_play_pt1(play: &str) -> Play {
+fn parse_play_pt1(input: &str, play: &str) -> Result<Play, ParseError> {
     match play {
-        "A" | "X" => Play::Rock,
-        "B" | "Y" => Play::Paper,
-        "C" | "Z" => Play::Scissors,
-        _ => unreachable!(),
+        "A" | "X" => Ok(Play::Rock),
+        "B" | "Y" => Ok(Play::Paper),
+        "C" | "Z" => Ok(Play::Scissors),
+        _ => Err(ParseError::locate(input, play, "one of A, B, C, X, Y, Z")),
     }
 }
 
 #[aoc_generator(day2, part2)]
-fn input_generator_pt2(input: &str) -> Vec<TargetRound> {
-    input.trim().split('\n').map(parse_round_pt2).collect()
+fn input_generator_pt2(input: &str) -> Result<Vec<TargetRound>, ParseError> {
+    input
+        .trim()
+        .lines()
+        .map(|l| parse_round_pt2(input, l))
+        .collect()
 }
 
-fn parse_round_pt2(s: &str) -> TargetRound {
-    let plays = s.split(' ').collect::<Vec<&str>>();
-    (parse_play_pt2(plays[0]), parse_target_result(plays[1]))
+fn parse_round_pt2(input: &str, s: &str) -> Result<TargetRound, ParseError> {
+    let (opp, target) = split_round(input, s)?;
+    Ok((
+        parse_play_pt2(input, opp)?,
+        parse_target_result(input, target)?,
+    ))
 }
 
-fn parse_play_pt2(play: &str) -> Play {
+fn parse_play_pt2(input: &str, play: &str) -> Result<Play, ParseError> {
     match play {
-        "A" => Play::Rock,
-        "B" => Play::Paper,
-        "C" => Play::Scissors,
-        _ => unimplemented!(),
+        "A" => Ok(Play::Rock),
+        "B" => Ok(Play::Paper),
+        "C" => Ok(Play::Scissors),
+        _ => Err(ParseError::locate(input, play, "one of A, B, C")),
     }
 }
 
-fn parse_target_result(target: &str) -> Results {
+fn parse_target_result(input: &str, target: &str) -> Result<Results, ParseError> {
     match target {
-        "X" => Results::Lose,
-        "Y" => Results::Draw,
-        "Z" => Results::Win,
-        _ => unimplemented!(),
+        "X" => Ok(Results::Lose),
+        "Y" => Ok(Results::Draw),
+        "Z" => Ok(Results::Win),
+        _ => Err(ParseError::locate(input, target, "one of X, Y, Z")),
     }
 }
 
+/// Split a round into its two columns.
+fn split_round<'a>(input: &str, s: &'a str) -> Result<(&'a str, &'a str), ParseError> {
+    s.split_once(' ')
+        .ok_or_else(|| ParseError::locate(input, &s[s.len()..], "a space between plays"))
+}
+
 #[aoc(day2, part1)]
 fn solve_d02_pt1(rounds: &[Round]) -> u32 {
     rounds.iter().map(points_for_round).sum()
@@ -69,7 +91,7 @@ fn solve_d02_pt2(target_rounds: &[TargetRound]) -> u32 {
 }
 
 #[derive(Debug, Copy, Clone)]
-enum Play {
+pub enum Play {
     Rock,
     Paper,
     Scissors,
@@ -79,7 +101,7 @@ type Round = (Play, Play);
 type TargetRound = (Play, Results);
 
 #[derive(Debug, Copy, Clone)]
-enum Results {
+pub enum Results {
     Win,
     Lose,
     Draw,
@@ -142,6 +164,30 @@ fn points_for_round(round: &Round) -> u32 {
     })
 }
 
+pub struct Day02;
+
+impl Puzzle for Day02 {
+    const DAY: u32 = 2;
+
+    /// Part 1 reads the second column as a shape to play, part 2 as the outcome to aim for.
+    type Input = (Vec<Round>, Vec<TargetRound>);
+    type Output1 = u32;
+    type Output2 = u32;
+    type Error = Infallible;
+
+    fn parse(&self, input: &str) -> Result<Self::Input, ParseError> {
+        Ok((input_generator_pt1(input)?, input_generator_pt2(input)?))
+    }
+
+    fn part1(&self, input: &Self::Input) -> Result<u32, Infallible> {
+        Ok(solve_d02_pt1(&input.0))
+    }
+
+    fn part2(&self, input: &Self::Input) -> Result<u32, Infallible> {
+        Ok(solve_d02_pt2(&input.1))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -153,7 +199,7 @@ C Z";
 
     #[test]
     fn example_pt1() {
-        let input = input_generator_pt1(EXAMPLE_INPUT);
+        let input = input_generator_pt1(EXAMPLE_INPUT).unwrap();
         let expect = 15;
         let actual = solve_d02_pt1(&input);
 
@@ -162,7 +208,7 @@ C Z";
 
     #[test]
     fn solve_input_pt1() {
-        let input = input_generator_pt1(FILE_INPUT);
+        let input = input_generator_pt1(FILE_INPUT).unwrap();
         let expect = 10624;
         let actual = solve_d02_pt1(&input);
 
@@ -171,7 +217,7 @@ C Z";
 
     #[test]
     fn example_pt2() {
-        let input = input_generator_pt2(EXAMPLE_INPUT);
+        let input = input_generator_pt2(EXAMPLE_INPUT).unwrap();
         let expect = 12;
         let actual = solve_d02_pt2(&input);
 
@@ -180,10 +226,18 @@ C Z";
 
     #[test]
     fn solve_input_pt2() {
-        let input = input_generator_pt2(FILE_INPUT);
+        let input = input_generator_pt2(FILE_INPUT).unwrap();
         let expect = 14060;
         let actual = solve_d02_pt2(&input);
 
         assert_eq!(expect, actual);
     }
+
+    #[test]
+    fn bad_play() {
+        let err = input_generator_pt1("A Y\nB Q").unwrap_err();
+        assert_eq!(ParseError::new(2, 3, "Q", "one of A, B, C, X, Y, Z"), err);
+        let err = input_generator_pt2("A Y\nBX").unwrap_err();
+        assert_eq!(ParseError::new(2, 3, "", "a space between plays"), err);
+    }
 }
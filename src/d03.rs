@@ -1,33 +1,57 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
 use hashbrown::HashSet;
 
+use crate::error::ParseError;
+use crate::solution::Puzzle;
+
 type Sack = (Vec<Item>, Vec<Item>);
 type Item = char;
 
 #[aoc_generator(day3, part1)]
-fn parse_input_pt1(input: &str) -> Vec<Sack> {
-    input.trim().lines().map(line_to_sack).collect()
+fn parse_input_pt1(input: &str) -> Result<Vec<Sack>, ParseError> {
+    input
+        .trim()
+        .lines()
+        .map(|l| line_to_sack(input, l))
+        .collect()
 }
 
-fn line_to_sack(line: &str) -> Sack {
+fn line_to_sack(input: &str, line: &str) -> Result<Sack, ParseError> {
+    let line = check_items(input, line)?;
     let length = line.len();
+    if length % 2 != 0 {
+        return Err(ParseError::locate(input, line, "an even number of items"));
+    }
     let chars = line.chars().collect::<Vec<Item>>();
-    (chars[0..length / 2].to_vec(), chars[length / 2..].to_vec())
+    Ok((chars[0..length / 2].to_vec(), chars[length / 2..].to_vec()))
+}
+
+/// Make sure every item in the line is an ASCII letter.
+fn check_items<'a>(input: &str, line: &'a str) -> Result<&'a str, ParseError> {
+    match line.find(|c: char| !c.is_ascii_alphabetic()) {
+        Some(i) => Err(ParseError::locate(input, &line[i..], "an item (a-z, A-Z)")),
+        None => Ok(line),
+    }
 }
 
 #[aoc(day3, part1)]
-fn solve_d03_pt1(sacks: &[Sack]) -> u32 {
-    sacks.iter().map(|s| item_priority(common_item(s))).sum()
+fn solve_d03_pt1(sacks: &[Sack]) -> Result<u32, MissingItem> {
+    sacks
+        .iter()
+        .enumerate()
+        .map(|(i, s)| {
+            common_item(s)
+                .map(item_priority)
+                .ok_or(MissingItem::Sack(i))
+        })
+        .sum()
 }
 
-fn common_item(sack: &Sack) -> Item {
+fn common_item(sack: &Sack) -> Option<Item> {
     let (comp_a, comp_b) = sack;
-    for item in comp_a {
-        if comp_b.contains(item) {
-            return *item;
-        }
-    }
-
-    unreachable!()
+    comp_a.iter().find(|item| comp_b.contains(item)).copied()
 }
 
 fn item_priority(item: Item) -> u32 {
@@ -39,33 +63,80 @@ fn item_priority(item: Item) -> u32 {
 }
 
 #[aoc_generator(day3, part2)]
-fn parse_input_pt2(input: &str) -> Vec<String> {
-    input.trim().lines().map(str::to_string).collect()
+fn parse_input_pt2(input: &str) -> Result<Vec<String>, ParseError> {
+    input
+        .trim()
+        .lines()
+        .map(|l| check_items(input, l).map(str::to_string))
+        .collect()
 }
 
 #[aoc(day3, part2)]
-fn solve_d03_pt2(sacks: &[String]) -> u32 {
-    sacks.chunks(3).map(group_value).sum()
+fn solve_d03_pt2(sacks: &[String]) -> Result<u32, MissingItem> {
+    sacks
+        .chunks(3)
+        .enumerate()
+        .map(|(i, group)| group_value(group).ok_or(MissingItem::Group(i)))
+        .sum()
 }
 
-fn group_value(sacks: &[String]) -> u32 {
-    item_priority(group_badge(sacks))
+fn group_value(sacks: &[String]) -> Option<u32> {
+    group_badge(sacks).map(item_priority)
 }
 
-fn group_badge(sacks: &[String]) -> Item {
-    let sets: Vec<HashSet<char>> = sacks.iter().map(|s| s.chars().collect()).collect();
+fn group_badge(sacks: &[String]) -> Option<Item> {
+    let mut sets = sacks.iter().map(|s| s.chars().collect::<HashSet<char>>());
+    let first = sets.next()?;
 
-    sets.iter()
-        .fold(HashSet::new(), |common, s| {
-            if common.is_empty() {
-                s.iter().copied().collect()
-            } else {
-                common.intersection(s).copied().collect()
-            }
-        })
-        .drain()
-        .next()
-        .unwrap()
+    sets.fold(first, |common, s| {
+        common.intersection(&s).copied().collect()
+    })
+    .drain()
+    .next()
+}
+
+/// An item that should be there but isn't, in the sack or group with the given 0-based index.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MissingItem {
+    /// No item is in both compartments of a sack.
+    Sack(usize),
+    /// No item is in every sack of a group, so it has no badge.
+    Group(usize),
+}
+
+impl Display for MissingItem {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            MissingItem::Sack(i) => write!(f, "sack {} has no item in both compartments", i),
+            MissingItem::Group(i) => write!(f, "group {} has no badge", i),
+        }
+    }
+}
+
+impl Error for MissingItem {}
+
+pub struct Day03;
+
+impl Puzzle for Day03 {
+    const DAY: u32 = 3;
+
+    /// Part 1 splits each sack into its compartments, part 2 groups whole sacks in threes.
+    type Input = (Vec<Sack>, Vec<String>);
+    type Output1 = u32;
+    type Output2 = u32;
+    type Error = MissingItem;
+
+    fn parse(&self, input: &str) -> Result<Self::Input, ParseError> {
+        Ok((parse_input_pt1(input)?, parse_input_pt2(input)?))
+    }
+
+    fn part1(&self, input: &Self::Input) -> Result<u32, MissingItem> {
+        solve_d03_pt1(&input.0)
+    }
+
+    fn part2(&self, input: &Self::Input) -> Result<u32, MissingItem> {
+        solve_d03_pt2(&input.1)
+    }
 }
 
 #[cfg(test)]
@@ -82,8 +153,8 @@ CrZsJsPPZsGzwwsLwLmpwMDw";
 
     #[test]
     fn example_pt1() {
-        let input = parse_input_pt1(EXAMPLE_INPUT);
-        let expect = 157;
+        let input = parse_input_pt1(EXAMPLE_INPUT).unwrap();
+        let expect = Ok(157);
         let actual = solve_d03_pt1(&input);
 
         assert_eq!(expect, actual);
@@ -91,8 +162,8 @@ CrZsJsPPZsGzwwsLwLmpwMDw";
 
     #[test]
     fn solve_input_pt1() {
-        let input = parse_input_pt1(FILE_INPUT);
-        let expect = 7848;
+        let input = parse_input_pt1(FILE_INPUT).unwrap();
+        let expect = Ok(7848);
         let actual = solve_d03_pt1(&input);
 
         assert_eq!(expect, actual);
@@ -100,8 +171,8 @@ CrZsJsPPZsGzwwsLwLmpwMDw";
 
     #[test]
     fn example_pt2() {
-        let input = parse_input_pt2(EXAMPLE_INPUT);
-        let expect = 70;
+        let input = parse_input_pt2(EXAMPLE_INPUT).unwrap();
+        let expect = Ok(70);
         let actual = solve_d03_pt2(&input);
 
         assert_eq!(expect, actual);
@@ -109,10 +180,28 @@ CrZsJsPPZsGzwwsLwLmpwMDw";
 
     #[test]
     fn solve_input_pt2() {
-        let input = parse_input_pt2(FILE_INPUT);
-        let expect = 2616;
+        let input = parse_input_pt2(FILE_INPUT).unwrap();
+        let expect = Ok(2616);
         let actual = solve_d03_pt2(&input);
 
         assert_eq!(expect, actual);
     }
+
+    #[test]
+    fn bad_items() {
+        let err = parse_input_pt1("vJrwpWtwJgWrhcsFMMfFFhFp\nabc").unwrap_err();
+        assert_eq!(ParseError::new(2, 1, "abc", "an even number of items"), err);
+        let err = parse_input_pt2("vJrw pWtw").unwrap_err();
+        assert_eq!(ParseError::new(1, 5, " pWtw", "an item (a-z, A-Z)"), err);
+    }
+
+    #[test]
+    fn missing_items() {
+        let input = parse_input_pt1("vJrwpWtwJgWrhcsFMMfFFhFp\nabcdef").unwrap();
+        assert_eq!(Err(MissingItem::Sack(1)), solve_d03_pt1(&input));
+        let input = parse_input_pt2("abc\nbcd\nade").unwrap();
+        assert_eq!(Err(MissingItem::Group(0)), solve_d03_pt2(&input));
+        let input = parse_input_pt2("abc\nbcd\ncde").unwrap();
+        assert_eq!(Ok(3), solve_d03_pt2(&input));
+    }
 }
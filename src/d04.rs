@@ -1,27 +1,36 @@
-use itertools::Itertools;
+use std::convert::Infallible;
+
+use crate::error::ParseError;
+use crate::solution::Puzzle;
 
 type Pair = (Assignment, Assignment);
 type Assignment = (u32, u32);
 
-fn parse_assignment(s: &str) -> Assignment {
-    s.split('-')
-        .map(|a| a.parse().unwrap())
-        .tuple_windows()
-        .next()
-        .unwrap()
+fn parse_assignment(input: &str, s: &str) -> Result<Assignment, ParseError> {
+    let (low, high) = s
+        .split_once('-')
+        .ok_or_else(|| ParseError::locate(input, s, "a range like `2-4`"))?;
+    Ok((parse_section(input, low)?, parse_section(input, high)?))
+}
+
+fn parse_section(input: &str, s: &str) -> Result<u32, ParseError> {
+    s.parse()
+        .map_err(|_| ParseError::locate(input, s, "a section number"))
 }
 
-fn parse_pair(s: &str) -> Pair {
-    s.split(',')
-        .map(parse_assignment)
-        .tuple_windows()
-        .next()
-        .unwrap()
+fn parse_pair(input: &str, s: &str) -> Result<Pair, ParseError> {
+    let (left, right) = s
+        .split_once(',')
+        .ok_or_else(|| ParseError::locate(input, &s[s.len()..], "`,` between assignments"))?;
+    Ok((
+        parse_assignment(input, left)?,
+        parse_assignment(input, right)?,
+    ))
 }
 
 #[aoc_generator(day4)]
-fn input_generator(input: &str) -> Vec<Pair> {
-    input.trim().lines().map(parse_pair).collect()
+fn input_generator(input: &str) -> Result<Vec<Pair>, ParseError> {
+    input.trim().lines().map(|l| parse_pair(input, l)).collect()
 }
 
 fn either_contains_other(p: &Pair) -> bool {
@@ -48,6 +57,29 @@ fn solve_d04_pt2(pairs: &Vec<Pair>) -> usize {
     pairs.iter().filter(|p| is_overlapping(p)).count()
 }
 
+pub struct Day04;
+
+impl Puzzle for Day04 {
+    const DAY: u32 = 4;
+
+    type Input = Vec<Pair>;
+    type Output1 = usize;
+    type Output2 = usize;
+    type Error = Infallible;
+
+    fn parse(&self, input: &str) -> Result<Self::Input, ParseError> {
+        input_generator(input)
+    }
+
+    fn part1(&self, pairs: &Self::Input) -> Result<usize, Infallible> {
+        Ok(solve_d04_pt1(pairs))
+    }
+
+    fn part2(&self, pairs: &Self::Input) -> Result<usize, Infallible> {
+        Ok(solve_d04_pt2(pairs))
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -62,7 +94,7 @@ mod tests {
 
     #[test]
     fn example_pt1() {
-        let input = input_generator(EXAMPLE_INPUT);
+        let input = input_generator(EXAMPLE_INPUT).unwrap();
         let expect = 2;
         let actual = solve_d04_pt1(&input);
 
@@ -71,7 +103,7 @@ mod tests {
 
     #[test]
     fn solve_pt1() {
-        let input = input_generator(FILE_INPUT);
+        let input = input_generator(FILE_INPUT).unwrap();
         let expect = 582;
         let actual = solve_d04_pt1(&input);
 
@@ -80,7 +112,7 @@ mod tests {
 
     #[test]
     fn example_pt2() {
-        let input = input_generator(EXAMPLE_INPUT);
+        let input = input_generator(EXAMPLE_INPUT).unwrap();
         let expect = 4;
         let actual = solve_d04_pt2(&input);
 
@@ -89,10 +121,18 @@ mod tests {
 
     #[test]
     fn solve_pt2() {
-        let input = input_generator(FILE_INPUT);
+        let input = input_generator(FILE_INPUT).unwrap();
         let expect = 893;
         let actual = solve_d04_pt2(&input);
 
         assert_eq!(expect, actual);
     }
+
+    #[test]
+    fn bad_pair() {
+        let err = input_generator("2-4,6-8\n2-3;4-5").unwrap_err();
+        assert_eq!(ParseError::new(2, 8, "", "`,` between assignments"), err);
+        let err = input_generator("2-4,6-x").unwrap_err();
+        assert_eq!(ParseError::new(1, 7, "x", "a section number"), err);
+    }
 }
@@ -1,53 +1,101 @@
 use std::collections::VecDeque;
-use std::num::ParseIntError;
-use std::str::FromStr;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
 
-#[aoc_generator(day5)]
-fn parse_input(input: &str) -> (Vec<Move>, Vec<Stack>) {
-    let parts = input.trim().split("\n\n").collect::<Vec<&str>>();
-    let moves = parts[1]
-        .lines()
-        .map(parser::parse_move)
-        .flatten()
-        .map(|(_, m)| m)
-        .collect::<Vec<Move>>();
+use crate::error::{parse_line, ParseError};
+use crate::solution::Puzzle;
 
-    dbg!(&moves);
+#[aoc_generator(day5)]
+fn parse_input(input: &str) -> Result<(Vec<Move>, Vec<Stack>), ParseError> {
+    let (drawing, moves) = input.split_once("\n\n").ok_or_else(|| {
+        ParseError::locate(
+            input,
+            &input[input.len()..],
+            "a blank line after the drawing",
+        )
+    })?;
 
-    let stack_rows = parts[0]
+    let moves = moves
         .lines()
-        .map(parser::parse_stack_row)
-        .flatten()
-        .map(|(_, r)| r)
-        .collect::<Vec<Vec<Option<Crate>>>>();
+        .map(|l| parse_line(input, l, parser::parse_move, "a move"))
+        .collect::<Result<Vec<Move>, ParseError>>()?;
 
-    dbg!(&stack_rows);
+    Ok((moves, parse_drawing(input, drawing)?))
+}
 
-    // recreate rows as columns
-    let stacks = todo!();
+/// Parse the stack drawing, a block of lines taken from `input`.
+fn parse_drawing(input: &str, drawing: &str) -> Result<Vec<Stack>, ParseError> {
+    // the last line of the drawing only numbers the stacks
+    let mut rows = drawing.lines().collect::<Vec<&str>>();
+    let numbers = rows.pop().unwrap_or(drawing);
+    let stack_ct = parse_line(input, numbers, parser::parse_stack_numbers, "stack numbers")?.len();
 
-    dbg!(&stacks);
+    let stack_rows = rows
+        .iter()
+        .map(|l| parse_line(input, l, parser::parse_stack_row, "`[A]` or `   `"))
+        .collect::<Result<Vec<Vec<Option<Crate>>>, ParseError>>()?;
+
+    // recreate rows as columns, filling each stack from the bottom up
+    let mut stacks = vec![Stack::new(); stack_ct];
+    for (line, row) in rows.iter().zip(&stack_rows).rev() {
+        for (i, c) in row.iter().enumerate() {
+            match (c, stacks.get_mut(i)) {
+                (Some(c), Some(stack)) => stack.push_back(c.clone()),
+                (Some(_), None) => {
+                    return Err(ParseError::locate(
+                        input,
+                        &line[i * 4..],
+                        "no crates past the last numbered stack",
+                    ))
+                }
+                (None, _) => {}
+            }
+        }
+    }
 
-    (moves, stacks)
+    Ok(stacks)
 }
 
 #[aoc(day5, part1)]
-fn solve_d05_pt1(input: &(Vec<Move>, Vec<Stack>)) -> String {
+fn solve_d05_pt1(input: &(Vec<Move>, Vec<Stack>)) -> Result<String, MoveError> {
     let (moves, stacks) = input;
-    todo!()
+    let mut crane = Crane::new(Model::CrateMover9000, stacks);
+    crane.apply_all(moves)?;
+    Ok(crane.top_labels())
 }
 
-pub type Stack = VecDeque<Option<Crate>>;
+#[aoc(day5, part2)]
+fn solve_d05_pt2(input: &(Vec<Move>, Vec<Stack>)) -> Result<String, MoveError> {
+    let (moves, stacks) = input;
+    let mut crane = Crane::new(Model::CrateMover9001, stacks);
+    crane.apply_all(moves)?;
+    Ok(crane.top_labels())
+}
 
-#[derive(Debug)]
+/// A stack of crates, bottom first.
+pub type Stack = VecDeque<Crate>;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
 pub struct Crate {
     label: char,
 }
 
+impl Crate {
+    pub fn new(label: char) -> Self {
+        Self { label }
+    }
+
+    pub fn label(&self) -> char {
+        self.label
+    }
+}
+
 #[derive(Debug)]
 pub struct Move {
     qty: usize,
+    /// 1-based number of the stack to take crates from.
     from: usize,
+    /// 1-based number of the stack to put crates on.
     to: usize,
 }
 
@@ -55,15 +103,219 @@ impl Move {
     pub fn new(qty: usize, from: usize, to: usize) -> Self {
         Self { qty, from, to }
     }
+
+    /// How many crates to move.
+    pub fn qty(&self) -> usize {
+        self.qty
+    }
+
+    pub fn from(&self) -> usize {
+        self.from
+    }
+
+    pub fn to(&self) -> usize {
+        self.to
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Model {
+    /// Moves crates one at a time, reversing their order.
+    CrateMover9000,
+    /// Moves all of a move's crates at once, keeping their order.
+    CrateMover9001,
+}
+
+/// Why a move couldn't be carried out. `index` is the 0-based position of the move in the list
+/// of moves applied.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MoveError {
+    /// The move named a stack that isn't in the drawing.
+    NoSuchStack { index: usize, stack: usize },
+    /// The move wanted more crates than its source stack holds.
+    NotEnoughCrates {
+        index: usize,
+        stack: usize,
+        held: usize,
+        wanted: usize,
+    },
+}
+
+impl Display for MoveError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            MoveError::NoSuchStack { index, stack } => {
+                write!(f, "move {}: there is no stack {}", index, stack)
+            }
+            MoveError::NotEnoughCrates {
+                index,
+                stack,
+                held,
+                wanted,
+            } => write!(
+                f,
+                "move {}: stack {} holds {} crates, {} short of the {} wanted",
+                index,
+                stack,
+                held,
+                wanted - held,
+                wanted
+            ),
+        }
+    }
+}
+
+impl Error for MoveError {}
+
+/// Rearranges a set of stacks by following moves.
+#[derive(Debug)]
+pub struct Crane {
+    model: Model,
+    stacks: Vec<Stack>,
+    /// How many moves have been applied so far.
+    applied: usize,
+}
+
+impl Crane {
+    pub fn new(model: Model, stacks: &[Stack]) -> Self {
+        Self {
+            model,
+            stacks: stacks.to_vec(),
+            applied: 0,
+        }
+    }
+
+    /// Carry out a move, leaving the stacks untouched if it's impossible.
+    pub fn apply(&mut self, m: &Move) -> Result<(), MoveError> {
+        let (from, to) = (self.stack_index(m.from)?, self.stack_index(m.to)?);
+        let held = self.stacks[from].len();
+        if held < m.qty {
+            return Err(MoveError::NotEnoughCrates {
+                index: self.applied,
+                stack: m.from,
+                held,
+                wanted: m.qty,
+            });
+        }
+
+        match self.model {
+            Model::CrateMover9000 => {
+                for _ in 0..m.qty {
+                    let c = self.stacks[from].pop_back().unwrap();
+                    self.stacks[to].push_back(c);
+                }
+            }
+            Model::CrateMover9001 => {
+                let at = self.stacks[from].len() - m.qty;
+                let lifted = self.stacks[from].split_off(at);
+                self.stacks[to].extend(lifted);
+            }
+        }
+        self.applied += 1;
+
+        Ok(())
+    }
+
+    pub fn apply_all(&mut self, moves: &[Move]) -> Result<(), MoveError> {
+        moves.iter().try_for_each(|m| self.apply(m))
+    }
+
+    /// Turn a 1-based stack number into an index into `stacks`.
+    fn stack_index(&self, stack: usize) -> Result<usize, MoveError> {
+        match stack {
+            n if (1..=self.stacks.len()).contains(&n) => Ok(n - 1),
+            _ => Err(MoveError::NoSuchStack {
+                index: self.applied,
+                stack,
+            }),
+        }
+    }
+
+    pub fn model(&self) -> Model {
+        self.model
+    }
+
+    pub fn stacks(&self) -> &[Stack] {
+        &self.stacks
+    }
+
+    /// Labels of the crate on top of each stack, skipping empty stacks.
+    pub fn top_labels(&self) -> String {
+        self.stacks
+            .iter()
+            .filter_map(|s| s.back())
+            .map(|c| c.label)
+            .collect()
+    }
+}
+
+impl Display for Crane {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "{}", render(&self.stacks))
+    }
+}
+
+/// Draw stacks the way the puzzle input does: one `[A]` (or `   `) per stack on each row, tallest
+/// stack first, followed by a row numbering the stacks. Rows keep their trailing spaces and have no
+/// trailing newline.
+pub fn render(stacks: &[Stack]) -> String {
+    let height = stacks.iter().map(|s| s.len()).max().unwrap_or(0);
+    let mut rows = (0..height)
+        .rev()
+        .map(|level| {
+            stacks
+                .iter()
+                .map(|s| match s.get(level) {
+                    Some(c) => format!("[{}]", c.label),
+                    None => "   ".to_string(),
+                })
+                .collect::<Vec<String>>()
+                .join(" ")
+        })
+        .collect::<Vec<String>>();
+
+    rows.push(
+        (1..=stacks.len())
+            .map(|n| format!(" {} ", n))
+            .collect::<Vec<String>>()
+            .join(" "),
+    );
+
+    rows.join("\n")
+}
+
+pub struct Day05;
+
+impl Puzzle for Day05 {
+    const DAY: u32 = 5;
+
+    type Input = (Vec<Move>, Vec<Stack>);
+    type Output1 = String;
+    type Output2 = String;
+    type Error = MoveError;
+
+    fn parse(&self, input: &str) -> Result<Self::Input, ParseError> {
+        parse_input(input)
+    }
+
+    fn part1(&self, input: &Self::Input) -> Result<String, MoveError> {
+        solve_d05_pt1(input)
+    }
+
+    fn part2(&self, input: &Self::Input) -> Result<String, MoveError> {
+        solve_d05_pt2(input)
+    }
 }
 
 mod parser {
     use super::{Crate, Move};
     use nom::{
-        bytes::complete::{tag, take},
-        character::complete::{alpha1, anychar, char, digit1, space0, space1},
-        combinator::{iterator, map_res, opt},
-        sequence::{delimited, preceded, terminated},
+        branch::alt,
+        bytes::complete::tag,
+        character::complete::{anychar, char, digit1, space0, space1},
+        combinator::{map, map_res, opt, value},
+        multi::many1,
+        sequence::{delimited, terminated},
         IResult,
     };
 
@@ -79,34 +331,29 @@ mod parser {
         terminated(map_res(digit1, |s: &str| s.parse()), space0)(input)
     }
 
+    pub fn parse_stack_numbers(input: &str) -> IResult<&str, Vec<usize>> {
+        many1(delimited(space0, map_res(digit1, str::parse), space0))(input)
+    }
+
     pub fn parse_stack_row(input: &str) -> IResult<&str, Vec<Option<Crate>>> {
         // crates in a row are chunks of 3 bytes followed by 0-1 space
-        let mut it = iterator(input, terminated(take(3usize), space0));
-        let row = it
-            .map(|e| {
-                let (i, c) = parse_crate(e).ok()?;
-                c
-            })
-            .collect::<Vec<Option<Crate>>>();
-        let (input, _) = it.finish()?;
-        Ok((input, row))
+        many1(terminated(parse_crate, opt(char(' '))))(input)
     }
 
     fn parse_crate(input: &str) -> IResult<&str, Option<Crate>> {
-        let (input, label) = opt(delimited(char('['), anychar, char(']')))(input)?;
-        Ok((input, {
-            if let Some(label) = label {
+        alt((
+            map(delimited(char('['), anychar, char(']')), |label| {
                 Some(Crate { label })
-            } else {
-                None
-            }
-        }))
+            }),
+            value(None, tag("   ")),
+        ))(input)
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     static EXAMPLE_INPUT: &str = "    [D]    
 [N] [C]    
@@ -121,9 +368,9 @@ move 1 from 1 to 2";
 
     #[test]
     fn example_pt1() {
-        let input = parse_input(EXAMPLE_INPUT);
-        let expect = " ";
-        let actual = solve_d05_pt1(&input);
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        let expect = "CMZ";
+        let actual = solve_d05_pt1(&input).unwrap();
 
         assert_eq!(expect, actual);
     }
@@ -131,28 +378,115 @@ move 1 from 1 to 2";
     #[ignore]
     #[test]
     fn solve_pt1() {
-        let input = parse_input(FILE_INPUT);
+        let input = parse_input(FILE_INPUT).unwrap();
+        let expect = " ";
+        let actual = solve_d05_pt1(&input).unwrap();
+
+        assert_eq!(expect, actual);
+    }
+
+    #[test]
+    fn example_pt2() {
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        let expect = "MCD";
+        let actual = solve_d05_pt2(&input).unwrap();
+
+        assert_eq!(expect, actual);
+    }
+
+    #[ignore]
+    #[test]
+    fn solve_pt2() {
+        let input = parse_input(FILE_INPUT).unwrap();
         let expect = " ";
-        let actual = solve_d05_pt1(&input);
+        let actual = solve_d05_pt2(&input).unwrap();
 
         assert_eq!(expect, actual);
     }
 
-    // #[test]
-    // fn example_pt2() {
-    //     let input = input_generator(EXAMPLE_INPUT);
-    //     let expect = 4;
-    //     let actual = solve_d04_pt2(&input);
+    #[test]
+    fn stacks_from_drawing() {
+        let (_, stacks) = parse_input(EXAMPLE_INPUT).unwrap();
+        let labels = stacks
+            .iter()
+            .map(|s| s.iter().map(|c| c.label).collect::<String>())
+            .collect::<Vec<String>>();
+        assert_eq!(vec!["ZN", "MCD", "P"], labels);
+    }
+
+    #[test]
+    fn render_example() {
+        let (moves, stacks) = parse_input(EXAMPLE_INPUT).unwrap();
+        let drawing = EXAMPLE_INPUT.split_once("\n\n").unwrap().0;
+        assert_eq!(drawing, render(&stacks));
+
+        let mut crane = Crane::new(Model::CrateMover9000, &stacks);
+        crane.apply(&moves[0]).unwrap();
+        let expect = "[D]        
+[N] [C]    
+[Z] [M] [P]
+ 1   2   3 ";
+        assert_eq!(expect, crane.to_string());
+
+        crane.apply(&moves[1]).unwrap();
+        let expect = "        [Z]
+        [N]
+    [C] [D]
+    [M] [P]
+ 1   2   3 ";
+        assert_eq!(expect, crane.to_string());
+    }
+
+    fn stacks() -> impl Strategy<Value = Vec<Stack>> {
+        let label = proptest::char::range('A', 'Z').prop_map(|label| Crate { label });
+        let stack = proptest::collection::vec_deque(label, 0..8);
+        proptest::collection::vec(stack, 1..10)
+    }
 
-    //     assert_eq!(expect, actual);
-    // }
+    proptest! {
+        #[test]
+        fn render_round_trip(stacks in stacks()) {
+            let drawing = render(&stacks);
+            prop_assert_eq!(&stacks, &parse_drawing(&drawing, &drawing).unwrap());
+        }
+    }
 
-    // #[test]
-    // fn solve_pt2() {
-    //     let input = input_generator(FILE_INPUT);
-    //     let expect = 893;
-    //     let actual = solve_d04_pt2(&input);
+    #[test]
+    fn bad_drawing() {
+        let err = parse_input("[A] [B\n 1   2 \n\nmove 1 from 1 to 2").unwrap_err();
+        assert_eq!(ParseError::new(1, 5, "[B", "`[A]` or `   `"), err);
+        let err = parse_input("[A]\n 1 \n\nmove 1 from 1 at 2").unwrap_err();
+        assert_eq!(ParseError::new(4, 15, "at 2", "a move"), err);
+        let err = parse_input("[A] [B]\n 1 \n\nmove 1 from 1 to 2").unwrap_err();
+        assert_eq!(
+            ParseError::new(1, 5, "[B]", "no crates past the last numbered stack"),
+            err
+        );
+    }
+
+    #[test]
+    fn impossible_moves() {
+        let (_, stacks) = parse_input(EXAMPLE_INPUT).unwrap();
+        let mut crane = Crane::new(Model::CrateMover9000, &stacks);
+        crane.apply(&Move::new(1, 2, 1)).unwrap();
+
+        let err = crane.apply(&Move::new(4, 1, 3)).unwrap_err();
+        let expect = MoveError::NotEnoughCrates {
+            index: 1,
+            stack: 1,
+            held: 3,
+            wanted: 4,
+        };
+        assert_eq!(expect, err);
+        assert_eq!(
+            "move 1: stack 1 holds 3 crates, 1 short of the 4 wanted",
+            err.to_string()
+        );
+        assert_eq!("DCP", crane.top_labels());
 
-    //     assert_eq!(expect, actual);
-    // }
+        let err = crane.apply(&Move::new(1, 0, 3)).unwrap_err();
+        assert_eq!(MoveError::NoSuchStack { index: 1, stack: 0 }, err);
+        let err = crane.apply_all(&[Move::new(1, 3, 1), Move::new(1, 2, 4)]);
+        assert_eq!(Err(MoveError::NoSuchStack { index: 2, stack: 4 }), err);
+    }
 }
@@ -1,8 +1,13 @@
 use std::collections::HashSet;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
 use std::ops::ControlFlow;
 
+use crate::error::ParseError;
+use crate::solution::Puzzle;
+
 #[aoc(day6, part1)]
-fn solve_d06_pt1(stream: &[u8]) -> usize {
+fn solve_d06_pt1(stream: &[u8]) -> Result<usize, NoMarker> {
     let mut i = 0;
 
     stream
@@ -17,11 +22,11 @@ fn solve_d06_pt1(stream: &[u8]) -> usize {
             }
         })
         .break_value()
-        .unwrap()
+        .ok_or(NoMarker)
 }
 
 #[aoc(day6, part2)]
-fn solve_d06_pt2(stream: &[u8]) -> usize {
+fn solve_d06_pt2(stream: &[u8]) -> Result<usize, NoMarker> {
     let mut i = 0;
 
     stream
@@ -36,7 +41,42 @@ fn solve_d06_pt2(stream: &[u8]) -> usize {
             }
         })
         .break_value()
-        .unwrap()
+        .ok_or(NoMarker)
+}
+
+/// The stream never has enough different characters in a row to make a marker.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct NoMarker;
+
+impl Display for NoMarker {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "the stream has no marker")
+    }
+}
+
+impl Error for NoMarker {}
+
+pub struct Day06;
+
+impl Puzzle for Day06 {
+    const DAY: u32 = 6;
+
+    type Input = Vec<u8>;
+    type Output1 = usize;
+    type Output2 = usize;
+    type Error = NoMarker;
+
+    fn parse(&self, input: &str) -> Result<Self::Input, ParseError> {
+        Ok(input.trim_end().as_bytes().to_vec())
+    }
+
+    fn part1(&self, stream: &Self::Input) -> Result<usize, NoMarker> {
+        solve_d06_pt1(stream)
+    }
+
+    fn part2(&self, stream: &Self::Input) -> Result<usize, NoMarker> {
+        solve_d06_pt2(stream)
+    }
 }
 
 #[cfg(test)]
@@ -49,17 +89,17 @@ mod tests {
 
     #[test]
     fn example_pt1() {
-        let expect = 7;
+        let expect = Ok(7);
         let actual = solve_d06_pt1(EXAMPLE_INPUT_1);
         assert_eq!(expect, actual);
-        let expect = 5;
+        let expect = Ok(5);
         let actual = solve_d06_pt1(EXAMPLE_INPUT_2);
         assert_eq!(expect, actual);
     }
 
     #[test]
     fn solve_pt1() {
-        let expect = 1794;
+        let expect = Ok(1794);
         let actual = solve_d06_pt1(FILE_INPUT);
 
         assert_eq!(expect, actual);
@@ -67,19 +107,25 @@ mod tests {
 
     #[test]
     fn example_pt2() {
-        let expect = 19;
+        let expect = Ok(19);
         let actual = solve_d06_pt2(EXAMPLE_INPUT_1);
         assert_eq!(expect, actual);
-        let expect = 23;
+        let expect = Ok(23);
         let actual = solve_d06_pt2(EXAMPLE_INPUT_2);
         assert_eq!(expect, actual);
     }
 
     #[test]
     fn solve_pt2() {
-        let expect = 2851;
+        let expect = Ok(2851);
         let actual = solve_d06_pt2(FILE_INPUT);
 
         assert_eq!(expect, actual);
     }
+
+    #[test]
+    fn no_marker() {
+        assert_eq!(Err(NoMarker), solve_d06_pt1(b"abcabcabc"));
+        assert_eq!(Err(NoMarker), solve_d06_pt2(b"abcdefghijklm"));
+    }
 }
@@ -1,19 +1,560 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use crate::error::{parse_line, ParseError};
+use crate::solution::Puzzle;
 use parser::Line;
 
 #[aoc_generator(day7)]
-fn parse_input(input: &str) -> Vec<Line> {
+fn parse_input(input: &str) -> Result<Vec<Line>, ParseError> {
     input
-        .trim()
+        .trim_end()
         .lines()
-        .flat_map(parser::output_line)
-        .map(|(_, l)| l)
+        .map(|l| parse_line(input, l, parser::output_line, "a command or listing"))
         .collect()
 }
 
+const DISK_SIZE: usize = 70_000_000;
+const UPDATE_SIZE: usize = 30_000_000;
+const SMALL_DIR_SIZE: usize = 100_000;
+
 #[aoc(day7, part1)]
 fn solve_d07_pt1(output: &[Line]) -> usize {
-    dbg!(&output);
-    todo!()
+    let fs = FileSystem::replay(output);
+    fs.dir_sizes()
+        .into_iter()
+        .map(|(_, size)| size)
+        .filter(|&size| size <= SMALL_DIR_SIZE)
+        .sum()
+}
+
+#[aoc(day7, part2)]
+fn solve_d07_pt2(output: &[Line]) -> Result<usize, Overfull> {
+    let fs = FileSystem::replay(output);
+    let sizes = fs.dir_sizes();
+    let used = fs.size(FileSystem::ROOT);
+    let free = DISK_SIZE.checked_sub(used).ok_or(Overfull { used })?;
+    let needed = UPDATE_SIZE.saturating_sub(free);
+    Ok(sizes
+        .into_iter()
+        .map(|(_, size)| size)
+        .filter(|&size| size >= needed)
+        .min()
+        .unwrap_or(0))
+}
+
+/// The files listed take up more than the whole disk.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Overfull {
+    pub used: usize,
+}
+
+impl Display for Overfull {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "the files take up {} bytes, more than the {} on the disk",
+            self.used, DISK_SIZE
+        )
+    }
+}
+
+impl Error for Overfull {}
+
+pub type NodeId = usize;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NodeKind {
+    Dir { children: Vec<NodeId> },
+    File { size: usize },
+}
+
+#[derive(Debug, Clone)]
+pub struct Node {
+    pub name: String,
+    /// `None` only for the root directory.
+    pub parent: Option<NodeId>,
+    pub kind: NodeKind,
+}
+
+/// The entries an `ls` showed, as names and file sizes (`None` for directories).
+type Listing = Vec<(String, Option<usize>)>;
+
+/// Something in a transcript that doesn't add up, found while replaying it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Diagnostic {
+    /// 1-based transcript line the problem was spotted on.
+    pub line: usize,
+    pub issue: Issue,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Issue {
+    /// `cd` into a directory no `ls` had shown (or that was listed as a file).
+    UnknownDir { path: String },
+    /// An `ls` of a directory that shows something different to its first `ls`.
+    ListingChanged { path: String },
+    /// A file listed again with a different size. The first size is kept.
+    SizeChanged {
+        path: String,
+        was: usize,
+        now: usize,
+    },
+    /// A directory name made of more than just letters.
+    UnusualName { name: String },
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "line {}: ", self.line)?;
+        match &self.issue {
+            Issue::UnknownDir { path } => {
+                write!(f, "cd into `{}`, which isn't a known directory", path)
+            }
+            Issue::ListingChanged { path } => {
+                write!(f, "`{}` lists differently than it did before", path)
+            }
+            Issue::SizeChanged { path, was, now } => {
+                write!(f, "`{}` was listed with size {}, now {}", path, was, now)
+            }
+            Issue::UnusualName { name } => {
+                write!(f, "directory name `{}` isn't only letters", name)
+            }
+        }
+    }
+}
+
+/// The directory tree as pieced together from a terminal transcript.
+///
+/// Nodes live in one `Vec` and refer to each other by index. A node is always added after its
+/// parent, so every child has a larger id than its parent.
+#[derive(Debug, Clone)]
+pub struct FileSystem {
+    nodes: Vec<Node>,
+}
+
+impl FileSystem {
+    pub const ROOT: NodeId = 0;
+
+    pub fn new() -> Self {
+        Self {
+            nodes: vec![Node {
+                name: "/".to_string(),
+                parent: None,
+                kind: NodeKind::Dir { children: vec![] },
+            }],
+        }
+    }
+
+    /// Rebuild the tree by following the `cd`s and recording whatever each `ls` lists.
+    pub fn replay(output: &[Line]) -> Self {
+        Self::replay_checked(output).0
+    }
+
+    /// Like [`FileSystem::replay`], but also report anything in the transcript that doesn't add
+    /// up. Replay carries on past each problem as best it can.
+    pub fn replay_checked(output: &[Line]) -> (Self, Vec<Diagnostic>) {
+        let mut fs = Self::new();
+        let mut cwd = Self::ROOT;
+        let mut diagnostics = vec![];
+        // what each directory looked like the first time it was listed
+        let mut listings: HashMap<NodeId, Listing> = HashMap::new();
+        // the `ls` being read: where it started, which directory and what it has shown so far
+        let mut listing: Option<(usize, NodeId, Listing)> = None;
+
+        let mut report = |line: usize, issue: Issue| diagnostics.push(Diagnostic { line, issue });
+
+        for (n, line) in output.iter().enumerate() {
+            let n = n + 1;
+
+            if matches!(line, Line::CmdCd(_) | Line::CmdLs) {
+                if let Some((ls_line, dir, entries)) = listing.take() {
+                    fs.check_listing(&mut listings, ls_line, dir, entries, &mut report);
+                }
+            }
+
+            match line {
+                Line::CmdCd(arg) => {
+                    cwd = match arg.as_str() {
+                        "/" => Self::ROOT,
+                        ".." => fs.nodes[cwd].parent.unwrap_or(Self::ROOT),
+                        name => {
+                            fs.check_name(n, name, &mut report);
+                            match fs.child(cwd, name) {
+                                Some(dir) if fs.is_dir(dir) => dir,
+                                found => {
+                                    report(
+                                        n,
+                                        Issue::UnknownDir {
+                                            path: fs.child_path(cwd, name),
+                                        },
+                                    );
+                                    match found {
+                                        Some(_) => cwd,
+                                        None => {
+                                            fs.add(cwd, name, NodeKind::Dir { children: vec![] })
+                                        }
+                                    }
+                                }
+                            }
+                        }
+                    }
+                }
+                Line::CmdLs => listing = Some((n, cwd, vec![])),
+                Line::Dir(name) => {
+                    fs.check_name(n, name, &mut report);
+                    if fs.child(cwd, name).is_none() {
+                        fs.add(cwd, name, NodeKind::Dir { children: vec![] });
+                    }
+                    if let Some((_, _, entries)) = &mut listing {
+                        entries.push((name.clone(), None));
+                    }
+                }
+                Line::File(size, name) => {
+                    match fs.child(cwd, name).map(|f| &fs.nodes[f].kind) {
+                        None => {
+                            fs.add(cwd, name, NodeKind::File { size: *size });
+                        }
+                        Some(&NodeKind::File { size: was }) if was != *size => report(
+                            n,
+                            Issue::SizeChanged {
+                                path: fs.child_path(cwd, name),
+                                was,
+                                now: *size,
+                            },
+                        ),
+                        Some(_) => {}
+                    }
+                    if let Some((_, _, entries)) = &mut listing {
+                        entries.push((name.clone(), Some(*size)));
+                    }
+                }
+            }
+        }
+
+        if let Some((ls_line, dir, entries)) = listing.take() {
+            fs.check_listing(&mut listings, ls_line, dir, entries, &mut report);
+        }
+
+        // listings are only checked once they end, so put them back in transcript order
+        diagnostics.sort_by_key(|d| d.line);
+        (fs, diagnostics)
+    }
+
+    /// Compare a finished `ls` of `dir` against the first one, if there was one.
+    fn check_listing(
+        &self,
+        listings: &mut HashMap<NodeId, Listing>,
+        ls_line: usize,
+        dir: NodeId,
+        mut entries: Listing,
+        report: &mut impl FnMut(usize, Issue),
+    ) {
+        entries.sort();
+        match listings.get(&dir) {
+            Some(first) if *first != entries => report(
+                ls_line,
+                Issue::ListingChanged {
+                    path: self.path(dir),
+                },
+            ),
+            Some(_) => {}
+            None => {
+                listings.insert(dir, entries);
+            }
+        }
+    }
+
+    fn check_name(&self, line: usize, name: &str, report: &mut impl FnMut(usize, Issue)) {
+        if name.is_empty() || !name.chars().all(|c| c.is_ascii_alphabetic()) {
+            report(
+                line,
+                Issue::UnusualName {
+                    name: name.to_string(),
+                },
+            );
+        }
+    }
+
+    fn is_dir(&self, id: NodeId) -> bool {
+        matches!(self.nodes[id].kind, NodeKind::Dir { .. })
+    }
+
+    /// The path `name` would have inside directory `dir`.
+    fn child_path(&self, dir: NodeId, name: &str) -> String {
+        match dir {
+            Self::ROOT => format!("/{}", name),
+            _ => format!("{}/{}", self.path(dir), name),
+        }
+    }
+
+    fn add(&mut self, parent: NodeId, name: &str, kind: NodeKind) -> NodeId {
+        let id = self.nodes.len();
+        self.nodes.push(Node {
+            name: name.to_string(),
+            parent: Some(parent),
+            kind,
+        });
+        if let NodeKind::Dir { children } = &mut self.nodes[parent].kind {
+            children.push(id);
+        }
+        id
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id]
+    }
+
+    pub fn children(&self, id: NodeId) -> &[NodeId] {
+        match &self.nodes[id].kind {
+            NodeKind::Dir { children } => children,
+            NodeKind::File { .. } => &[],
+        }
+    }
+
+    /// Look up an entry of directory `dir` by name.
+    pub fn child(&self, dir: NodeId, name: &str) -> Option<NodeId> {
+        self.children(dir)
+            .iter()
+            .copied()
+            .find(|&c| self.nodes[c].name == name)
+    }
+
+    /// The full path of a node, e.g. `/a/e`.
+    pub fn path(&self, id: NodeId) -> String {
+        match self.nodes[id].parent {
+            None => "/".to_string(),
+            Some(Self::ROOT) => format!("/{}", self.nodes[id].name),
+            Some(parent) => format!("{}/{}", self.path(parent), self.nodes[id].name),
+        }
+    }
+
+    /// Size of a file, or the total size of everything below a directory.
+    pub fn size(&self, id: NodeId) -> usize {
+        match &self.nodes[id].kind {
+            NodeKind::File { size } => *size,
+            NodeKind::Dir { children } => children.iter().map(|&c| self.size(c)).sum(),
+        }
+    }
+
+    /// Recursive size of every directory, root first.
+    pub fn dir_sizes(&self) -> Vec<(NodeId, usize)> {
+        let sizes = self.sizes();
+        self.nodes
+            .iter()
+            .enumerate()
+            .filter(|(_, n)| matches!(n.kind, NodeKind::Dir { .. }))
+            .map(|(id, _)| (id, sizes[id]))
+            .collect()
+    }
+}
+
+/// Reports for poking around the reconstructed tree, modelled on `tree`, `du` and `find`.
+impl FileSystem {
+    /// Number of directories between the root and a node, the root itself being at depth 0.
+    pub fn depth(&self, id: NodeId) -> usize {
+        match self.nodes[id].parent {
+            None => 0,
+            Some(parent) => self.depth(parent) + 1,
+        }
+    }
+
+    /// Every node below the root, with the root first and each directory's entries following it
+    /// in the order they were listed.
+    pub fn walk(&self) -> Vec<NodeId> {
+        let mut order = vec![];
+        let mut stack = vec![Self::ROOT];
+        while let Some(id) = stack.pop() {
+            order.push(id);
+            stack.extend(self.children(id).iter().rev());
+        }
+        order
+    }
+
+    /// The whole tree, one indented line per node, annotated with sizes:
+    ///
+    /// ```text
+    /// - / (dir, size=48381165)
+    ///   - a (dir, size=94853)
+    ///     - e (dir, size=584)
+    ///       - i (file, size=584)
+    /// ```
+    pub fn tree(&self) -> String {
+        let sizes = self.sizes();
+        self.walk()
+            .into_iter()
+            .map(|id| {
+                let node = &self.nodes[id];
+                let kind = match node.kind {
+                    NodeKind::Dir { .. } => "dir",
+                    NodeKind::File { .. } => "file",
+                };
+                format!(
+                    "{}- {} ({}, size={})",
+                    "  ".repeat(self.depth(id)),
+                    node.name,
+                    kind,
+                    sizes[id]
+                )
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Directories no deeper than `max_depth`, largest first, like `du -d max_depth`.
+    pub fn du(&self, max_depth: usize) -> Vec<(NodeId, usize)> {
+        let mut dirs = self
+            .dir_sizes()
+            .into_iter()
+            .filter(|&(id, _)| self.depth(id) <= max_depth)
+            .collect::<Vec<(NodeId, usize)>>();
+        dirs.sort_by(|(a, a_size), (b, b_size)| {
+            b_size
+                .cmp(a_size)
+                .then_with(|| self.path(*a).cmp(&self.path(*b)))
+        });
+        dirs
+    }
+
+    /// [`FileSystem::du`] as `size<TAB>path` lines.
+    pub fn du_report(&self, max_depth: usize) -> String {
+        self.du(max_depth)
+            .into_iter()
+            .map(|(id, size)| format!("{}\t{}", size, self.path(id)))
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// Every node matching all of `query`'s conditions, in [`FileSystem::walk`] order.
+    pub fn find(&self, query: &Find) -> Vec<NodeId> {
+        let sizes = self.sizes();
+        self.walk()
+            .into_iter()
+            .filter(|&id| query.matches(&self.nodes[id], sizes[id]))
+            .collect()
+    }
+
+    /// Size of every node, indexed by id.
+    fn sizes(&self) -> Vec<usize> {
+        // children come after their parents, so walking backwards totals every child before
+        // its parent is passed up
+        let mut sizes = vec![0; self.nodes.len()];
+        for id in (0..self.nodes.len()).rev() {
+            if let NodeKind::File { size } = self.nodes[id].kind {
+                sizes[id] = size;
+            }
+            if let Some(parent) = self.nodes[id].parent {
+                sizes[parent] += sizes[id];
+            }
+        }
+        sizes
+    }
+}
+
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum EntryType {
+    Dir,
+    File,
+}
+
+/// Conditions for [`FileSystem::find`], built up like the arguments to `find`. An empty query
+/// matches everything.
+#[derive(Debug, Clone, Default)]
+pub struct Find {
+    name: Option<String>,
+    entry_type: Option<EntryType>,
+    min_size: Option<usize>,
+    max_size: Option<usize>,
+}
+
+impl Find {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Match names against a glob, where `*` matches any run of characters and `?` any one.
+    pub fn name(mut self, glob: &str) -> Self {
+        self.name = Some(glob.to_string());
+        self
+    }
+
+    pub fn entry_type(mut self, entry_type: EntryType) -> Self {
+        self.entry_type = Some(entry_type);
+        self
+    }
+
+    /// Match nodes at least `size` big; directories count everything below them.
+    pub fn min_size(mut self, size: usize) -> Self {
+        self.min_size = Some(size);
+        self
+    }
+
+    /// Match nodes at most `size` big; directories count everything below them.
+    pub fn max_size(mut self, size: usize) -> Self {
+        self.max_size = Some(size);
+        self
+    }
+
+    fn matches(&self, node: &Node, size: usize) -> bool {
+        let entry_type = match node.kind {
+            NodeKind::Dir { .. } => EntryType::Dir,
+            NodeKind::File { .. } => EntryType::File,
+        };
+        self.name.as_ref().is_none_or(|g| glob_match(g, &node.name))
+            && self.entry_type.is_none_or(|t| t == entry_type)
+            && self.min_size.is_none_or(|min| size >= min)
+            && self.max_size.is_none_or(|max| size <= max)
+    }
+}
+
+fn glob_match(glob: &str, name: &str) -> bool {
+    let glob = glob.chars().collect::<Vec<char>>();
+    let name = name.chars().collect::<Vec<char>>();
+    // matched[j] is whether the glob so far matches the first j characters of name
+    let mut matched = vec![false; name.len() + 1];
+    matched[0] = true;
+    for g in glob {
+        let mut next = vec![false; name.len() + 1];
+        for j in 0..=name.len() {
+            next[j] = match g {
+                '*' => matched[j] || (j > 0 && next[j - 1]),
+                '?' => j > 0 && matched[j - 1],
+                c => j > 0 && matched[j - 1] && name[j - 1] == c,
+            };
+        }
+        matched = next;
+    }
+    matched[name.len()]
+}
+
+impl Default for FileSystem {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+pub struct Day07;
+
+impl Puzzle for Day07 {
+    const DAY: u32 = 7;
+
+    type Input = Vec<Line>;
+    type Output1 = usize;
+    type Output2 = usize;
+    type Error = Overfull;
+
+    fn parse(&self, input: &str) -> Result<Self::Input, ParseError> {
+        parse_input(input)
+    }
+
+    fn part1(&self, output: &Self::Input) -> Result<usize, Overfull> {
+        Ok(solve_d07_pt1(output))
+    }
+
+    fn part2(&self, output: &Self::Input) -> Result<usize, Overfull> {
+        solve_d07_pt2(output)
+    }
 }
 
 mod parser {
@@ -21,9 +562,9 @@ mod parser {
     use nom::{
         branch::alt,
         bytes::complete::tag,
-        character::complete::{alpha1, char, digit1, space1},
-        combinator::{rest, value},
-        sequence::{pair, preceded, terminated},
+        character::complete::{char, digit1, space1},
+        combinator::{map_res, rest, value, verify},
+        sequence::{pair, preceded, separated_pair},
         IResult,
     };
 
@@ -40,7 +581,7 @@ mod parser {
     }
 
     fn cd(i: &str) -> IResult<&str, Line> {
-        let (i, arg) = preceded(pair(tag("cd"), space1), rest)(i)?;
+        let (i, arg) = preceded(pair(tag("cd"), space1), name)(i)?;
         Ok((i, Line::CmdCd(arg.to_string())))
     }
 
@@ -49,20 +590,25 @@ mod parser {
     }
 
     fn plain(i: &str) -> IResult<&str, Line> {
-        let (i, size_str) = terminated(digit1, pair(space1, rest))(i)?;
-        Ok((i, Line::File(size_str.parse::<usize>().unwrap())))
+        let (i, (size, name)) = separated_pair(map_res(digit1, str::parse), space1, name)(i)?;
+        Ok((i, Line::File(size, name.to_string())))
     }
 
     fn dir(i: &str) -> IResult<&str, Line> {
-        let (i, label) = preceded(pair(tag("dir"), space1), alpha1)(i)?;
+        let (i, label) = preceded(pair(tag("dir"), space1), name)(i)?;
         Ok((i, Line::Dir(label.to_string())))
     }
 
+    /// Whatever is left of the line, which has to be something.
+    fn name(i: &str) -> IResult<&str, &str> {
+        verify(rest, |s: &str| !s.is_empty())(i)
+    }
+
     #[derive(Debug, Clone)]
     pub enum Line {
         CmdLs,
         CmdCd(String),
-        File(usize),
+        File(usize, String),
         Dir(String),
     }
 }
@@ -98,35 +644,201 @@ $ ls
 
     #[test]
     fn example_pt1() {
-        let input = parse_input(EXAMPLE_INPUT);
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
         let expect = 95437;
         let actual = solve_d07_pt1(&input);
         assert_eq!(expect, actual);
     }
 
+    #[ignore]
     #[test]
     fn solve_pt1() {
-        let input = parse_input(FILE_INPUT);
+        let input = parse_input(FILE_INPUT).unwrap();
         let expect = 0;
         let actual = solve_d07_pt1(&input);
         assert_eq!(expect, actual);
     }
 
-    // #[ignore]
-    // #[test]
-    // fn example_pt2() {
-    //     let input = parse_input(EXAMPLE_INPUT);
-    //     let expect = 0;
-    //     let actual = solve_d07_pt1(&input);
-    //     assert_eq!(expect, actual);
-    // }
-
-    // #[ignore]
-    // #[test]
-    // fn solve_pt2() {
-    //     let input = parse_input(FILE_INPUT);
-    //     let expect = 0;
-    //     let actual = solve_d07_pt1(&input);
-    //     assert_eq!(expect, actual);
-    // }
+    #[test]
+    fn example_pt2() {
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        let expect = Ok(24933642);
+        let actual = solve_d07_pt2(&input);
+        assert_eq!(expect, actual);
+    }
+
+    #[ignore]
+    #[test]
+    fn solve_pt2() {
+        let input = parse_input(FILE_INPUT).unwrap();
+        let expect = Ok(0);
+        let actual = solve_d07_pt2(&input);
+        assert_eq!(expect, actual);
+    }
+
+    #[test]
+    fn overfull_disk() {
+        let input = parse_input("$ cd /\n$ ls\n40000000 a\n40000000 b").unwrap();
+        assert_eq!(Err(Overfull { used: 80_000_000 }), solve_d07_pt2(&input));
+    }
+
+    #[test]
+    fn replay_example() {
+        let fs = FileSystem::replay(&parse_input(EXAMPLE_INPUT).unwrap());
+        let e = fs
+            .child(fs.child(FileSystem::ROOT, "a").unwrap(), "e")
+            .unwrap();
+        assert_eq!("/a/e", fs.path(e));
+        assert_eq!(584, fs.size(e));
+        let i = fs.child(e, "i").unwrap();
+        assert_eq!(NodeKind::File { size: 584 }, fs.node(i).kind);
+        assert_eq!(48381165, fs.size(FileSystem::ROOT));
+
+        let sizes = fs
+            .dir_sizes()
+            .into_iter()
+            .map(|(id, size)| (fs.path(id), size))
+            .collect::<Vec<(String, usize)>>();
+        let expect = [
+            ("/", 48381165),
+            ("/a", 94853),
+            ("/d", 24933642),
+            ("/a/e", 584),
+        ];
+        assert_eq!(expect.map(|(p, s)| (p.to_string(), s)).to_vec(), sizes);
+    }
+
+    #[test]
+    fn tree_example() {
+        let fs = FileSystem::replay(&parse_input(EXAMPLE_INPUT).unwrap());
+        let expect = "- / (dir, size=48381165)
+  - a (dir, size=94853)
+    - e (dir, size=584)
+      - i (file, size=584)
+    - f (file, size=29116)
+    - g (file, size=2557)
+    - h.lst (file, size=62596)
+  - b.txt (file, size=14848514)
+  - c.dat (file, size=8504156)
+  - d (dir, size=24933642)
+    - j (file, size=4060174)
+    - d.log (file, size=8033020)
+    - d.ext (file, size=5626152)
+    - k (file, size=7214296)";
+        assert_eq!(expect, fs.tree());
+    }
+
+    #[test]
+    fn du_example() {
+        let fs = FileSystem::replay(&parse_input(EXAMPLE_INPUT).unwrap());
+        assert_eq!("48381165\t/\n24933642\t/d\n94853\t/a", fs.du_report(1));
+        assert_eq!("48381165\t/", fs.du_report(0));
+        assert_eq!(4, fs.du(5).len());
+    }
+
+    #[test]
+    fn find_example() {
+        let fs = FileSystem::replay(&parse_input(EXAMPLE_INPUT).unwrap());
+        let paths = |q: Find| {
+            fs.find(&q)
+                .into_iter()
+                .map(|id| fs.path(id))
+                .collect::<Vec<String>>()
+        };
+        assert_eq!(vec!["/d/d.log", "/d/d.ext"], paths(Find::new().name("d.*")));
+        assert_eq!(
+            vec!["/a/h.lst", "/d/d.log"],
+            paths(Find::new().name("*.l*"))
+        );
+        assert_eq!(vec!["/b.txt"], paths(Find::new().name("?.t?t")));
+        assert_eq!(
+            vec!["/a", "/a/e"],
+            paths(Find::new().entry_type(EntryType::Dir).max_size(100_000))
+        );
+        assert_eq!(
+            vec!["/b.txt", "/c.dat", "/d/d.log"],
+            paths(Find::new().entry_type(EntryType::File).min_size(8_000_000))
+        );
+        assert_eq!(14, paths(Find::new()).len());
+    }
+
+    #[test]
+    fn consistent_example() {
+        let (_, diagnostics) = FileSystem::replay_checked(&parse_input(EXAMPLE_INPUT).unwrap());
+        assert_eq!(Vec::<Diagnostic>::new(), diagnostics);
+    }
+
+    #[test]
+    fn inconsistent_transcript() {
+        let input = "$ cd /
+$ ls
+dir a
+dir b.old
+14848514 b.txt
+$ cd c
+$ cd /
+$ ls
+dir a
+dir b.old
+14848515 b.txt
+$ cd b.old
+$ ls";
+        let (fs, diagnostics) = FileSystem::replay_checked(&parse_input(input).unwrap());
+        let expect = vec![
+            Diagnostic {
+                line: 4,
+                issue: Issue::UnusualName {
+                    name: "b.old".to_string(),
+                },
+            },
+            Diagnostic {
+                line: 6,
+                issue: Issue::UnknownDir {
+                    path: "/c".to_string(),
+                },
+            },
+            Diagnostic {
+                line: 8,
+                issue: Issue::ListingChanged {
+                    path: "/".to_string(),
+                },
+            },
+            Diagnostic {
+                line: 10,
+                issue: Issue::UnusualName {
+                    name: "b.old".to_string(),
+                },
+            },
+            Diagnostic {
+                line: 11,
+                issue: Issue::SizeChanged {
+                    path: "/b.txt".to_string(),
+                    was: 14848514,
+                    now: 14848515,
+                },
+            },
+            Diagnostic {
+                line: 12,
+                issue: Issue::UnusualName {
+                    name: "b.old".to_string(),
+                },
+            },
+        ];
+        assert_eq!(expect, diagnostics);
+        assert_eq!(
+            "line 11: `/b.txt` was listed with size 14848514, now 14848515",
+            diagnostics[4].to_string()
+        );
+        assert_eq!(14848514, fs.size(FileSystem::ROOT));
+        assert!(fs.child(FileSystem::ROOT, "c").is_some());
+    }
+
+    #[test]
+    fn bad_line() {
+        let err = parse_input("$ cd /\n$ rm -rf").unwrap_err();
+        assert_eq!(
+            ParseError::new(2, 1, "$ rm -rf", "a command or listing"),
+            err
+        );
+    }
 }
@@ -1,14 +1,27 @@
+use std::convert::Infallible;
 use std::ops::ControlFlow;
 
+use crate::error::{check_grid, ParseError};
+use crate::solution::Puzzle;
+
 #[aoc_generator(day8)]
-fn parse_input(input: &str) -> Vec<Vec<u8>> {
-    input.trim().lines().map(parse_line).collect()
+fn parse_input(input: &str) -> Result<Vec<Vec<u8>>, ParseError> {
+    let rows = input.trim().lines().map(str::trim).collect::<Vec<&str>>();
+    let trees = rows
+        .iter()
+        .map(|l| parse_line(input, l))
+        .collect::<Result<Vec<Vec<u8>>, ParseError>>()?;
+    check_grid(input, &rows, "tree height")?;
+    Ok(trees)
 }
 
-fn parse_line(line: &str) -> Vec<u8> {
-    line.trim()
-        .chars()
-        .map(|c| c.to_digit(10).unwrap() as u8)
+fn parse_line(input: &str, line: &str) -> Result<Vec<u8>, ParseError> {
+    line.char_indices()
+        .map(|(i, c)| {
+            c.to_digit(10)
+                .map(|d| d as u8)
+                .ok_or_else(|| ParseError::locate(input, &line[i..], "a tree height (0-9)"))
+        })
         .collect()
 }
 
@@ -107,6 +120,29 @@ fn solve_d08_pt2(trees: &Vec<Vec<u8>>) -> usize {
     max_scenic_score
 }
 
+pub struct Day08;
+
+impl Puzzle for Day08 {
+    const DAY: u32 = 8;
+
+    type Input = Vec<Vec<u8>>;
+    type Output1 = usize;
+    type Output2 = usize;
+    type Error = Infallible;
+
+    fn parse(&self, input: &str) -> Result<Self::Input, ParseError> {
+        parse_input(input)
+    }
+
+    fn part1(&self, trees: &Self::Input) -> Result<usize, Infallible> {
+        Ok(solve_d08_pt1(trees))
+    }
+
+    fn part2(&self, trees: &Self::Input) -> Result<usize, Infallible> {
+        Ok(solve_d08_pt2(trees))
+    }
+}
+
 mod tests {
     use super::*;
 
@@ -119,7 +155,7 @@ mod tests {
 
     #[test]
     fn example_pt1() {
-        let input = parse_input(EXAMPLE_INPUT);
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
         let expect = 21;
         let actual = solve_d08_pt1(&input);
         assert_eq!(expect, actual);
@@ -127,7 +163,7 @@ mod tests {
 
     #[test]
     fn solve_pt1() {
-        let input = parse_input(FILE_INPUT);
+        let input = parse_input(FILE_INPUT).unwrap();
         let expect = 1669;
         let actual = solve_d08_pt1(&input);
         assert_eq!(expect, actual);
@@ -135,7 +171,7 @@ mod tests {
 
     #[test]
     fn example_pt2() {
-        let input = parse_input(EXAMPLE_INPUT);
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
         let expect = 8;
         let actual = solve_d08_pt2(&input);
         assert_eq!(expect, actual);
@@ -143,9 +179,23 @@ mod tests {
 
     #[test]
     fn solve_pt2() {
-        let input = parse_input(FILE_INPUT);
+        let input = parse_input(FILE_INPUT).unwrap();
         let expect = 331344;
         let actual = solve_d08_pt2(&input);
         assert_eq!(expect, actual);
     }
+
+    #[test]
+    fn bad_height() {
+        let err = parse_input("303\n2x5").unwrap_err();
+        assert_eq!(ParseError::new(2, 2, "x5", "a tree height (0-9)"), err);
+    }
+
+    #[test]
+    fn ragged_grid() {
+        let err = parse_input("303\n25\n653").unwrap_err();
+        assert_eq!(ParseError::new(2, 3, "", "a row of 3 tree heights"), err);
+        let err = parse_input("").unwrap_err();
+        assert_eq!(ParseError::new(1, 1, "", "a grid of tree heights"), err);
+    }
 }
@@ -1,61 +1,130 @@
-use std::cmp;
-use std::fmt::{Debug, Formatter, Result as FmtResult};
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+use std::io::{self, Write};
+use std::iter;
 use std::str::FromStr;
+use std::thread;
+use std::time::Duration;
 
-/**
+use crate::error::ParseError;
+use crate::solution::Puzzle;
+
+/*
  * Approach:
  * - Parse moves
- * - Use moves to calculate size of grid to allocate/initialize
- * - Simulate tail movements from head movements, tracking number of unvisited cells as they are visited
+ * - Simulate each knot following the one ahead of it from head movements, tracking the cells the
+ *   tail visits in a sparse grid that only takes up memory where the tail has been
  * - Return number of unique visits
  */
 
-struct RopeBoard {
-    /// The 2D Vec used to track tail visitations
-    pub grid: Vec<Vec<bool>>,
+pub struct RopeBoard {
+    /// The cells the tail has visited
+    visited: Visited,
     /// List of rope head moves
     pub moves: Vec<Move>,
-    /// Convenience variable for storing and accessing the location of the rope head
-    head: Point,
-    /// Convenience variable for storing and accessing the location of the rope end
-    tail: Point,
+    /// The location of every knot in the rope, from head to tail
+    knots: Vec<Point>,
 }
 
-impl Debug for RopeBoard {
+/// Draws the whole board, see [`RopeBoard::render`].
+impl Display for RopeBoard {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
-        for y in 0..self.grid.len() {
-            for x in 0..self.grid[0].len() {
-                let c = self.grid[y][x];
-                if self.head.x == x as i32 && self.tail.y == y as i32 {
-                    write!(f, "(H");
-                } else if self.tail.x == x as i32 && self.tail.y == y as i32 {
-                    write!(f, "T)");
-                } else if c {
-                    write!(f, "##");
-                } else {
-                    write!(f, "__");
-                }
-            }
-            write!(f, "\n");
-        }
-        write!(f, "\n")
+        write!(f, "{}", self.render(Viewport::Fit))
+    }
+}
+
+/// Which part of the board a frame shows.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Viewport {
+    /// Just big enough for the start, every knot and every visited cell.
+    Fit,
+    /// Everything from `min` to `max` inclusive, whatever is going on elsewhere.
+    Fixed { min: Point, max: Point },
+    /// A `width` by `height` window kept centred on the head.
+    Follow { width: i64, height: i64 },
+}
+
+/// Width and height of a [`Visited`] chunk, one bit per cell.
+const CHUNK: i64 = 64;
+
+/// A sparse grid of cells, stored as square chunks of bits allocated as they're first touched.
+#[derive(Debug, Default)]
+struct Visited {
+    /// Chunks keyed by their position in chunks, one row of bits per `u64`
+    chunks: HashMap<(i64, i64), [u64; CHUNK as usize]>,
+    /// Convenience variable for the number of cells visited
+    count: usize,
+}
+
+impl Visited {
+    /// Mark `p` as visited, returning whether it wasn't already.
+    fn insert(&mut self, p: Point) -> bool {
+        let chunk = self
+            .chunks
+            .entry((p.x.div_euclid(CHUNK), p.y.div_euclid(CHUNK)))
+            .or_insert([0; CHUNK as usize]);
+        let row = &mut chunk[p.y.rem_euclid(CHUNK) as usize];
+        let bit = 1 << p.x.rem_euclid(CHUNK);
+        let new = *row & bit == 0;
+        *row |= bit;
+        self.count += new as usize;
+        new
+    }
+
+    fn contains(&self, p: Point) -> bool {
+        self.chunks
+            .get(&(p.x.div_euclid(CHUNK), p.y.div_euclid(CHUNK)))
+            .is_some_and(|chunk| {
+                chunk[p.y.rem_euclid(CHUNK) as usize] & 1 << p.x.rem_euclid(CHUNK) != 0
+            })
+    }
+
+    fn len(&self) -> usize {
+        self.count
+    }
+
+    /// Every visited cell, in no particular order.
+    fn iter(&self) -> impl Iterator<Item = Point> + '_ {
+        self.chunks.iter().flat_map(|(&(cx, cy), chunk)| {
+            chunk.iter().enumerate().flat_map(move |(y, &row)| {
+                (0..CHUNK)
+                    .filter(move |x| row & 1 << x != 0)
+                    .map(move |x| Point::new(cx * CHUNK + x, cy * CHUNK + y as i64))
+            })
+        })
+    }
+
+    /// The corners of the smallest box around every visited cell, or the origin if there are none.
+    fn bounds(&self) -> (Point, Point) {
+        let mut cells = self.iter();
+        let Some(first) = cells.next() else {
+            return (Point::new(0, 0), Point::new(0, 0));
+        };
+        cells.fold((first, first), |(min, max), p| {
+            (
+                Point::new(min.x.min(p.x), min.y.min(p.y)),
+                Point::new(max.x.max(p.x), max.y.max(p.y)),
+            )
+        })
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-struct Point {
-    x: i32,
-    y: i32,
+/// A cell of the board, with `y` going up and the rope starting out at the origin.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub struct Point {
+    pub x: i64,
+    pub y: i64,
 }
 
 impl Point {
-    fn new(x: i32, y: i32) -> Self {
+    pub fn new(x: i64, y: i64) -> Self {
         Self { x, y }
     }
 
     /// # Panics
-    /// If move would overflow usize.
-    fn move_direction(&mut self, dir: Dir, d: i32) {
+    /// If move would overflow i64.
+    fn move_direction(&mut self, dir: Dir, d: i64) {
         match dir {
             Dir::U => self.y = self.y.checked_add(d).unwrap(),
             Dir::D => self.y = self.y.checked_sub(d).unwrap(),
@@ -64,85 +133,151 @@ impl Point {
         }
     }
 
-    fn diff(&self, lhs: &Point) -> usize {
-        (((self.x as isize - lhs.x as isize).pow(2) + (self.y as isize - lhs.y as isize).pow(2))
-            as f32)
-            .sqrt() as usize
+    /// Catch up with `leader` if it's no longer touching, moving one step toward it along each
+    /// axis it's off by, so diagonally when it's in neither the same row nor column.
+    fn follow(&mut self, leader: &Point) {
+        let (dx, dy) = (leader.x - self.x, leader.y - self.y);
+        if dx.abs() > 1 || dy.abs() > 1 {
+            self.x += dx.signum();
+            self.y += dy.signum();
+        }
     }
 }
 
 impl RopeBoard {
-    fn new(moves: &Vec<Move>) -> Self {
-        let ((w, h), origin) = RopeBoard::calc_dimensions_and_origin(moves);
+    /// # Panics
+    /// If the rope has fewer than 2 knots.
+    pub fn new(moves: &[Move], knots: usize) -> Self {
+        assert!(knots >= 2, "a rope needs a head and a tail");
+        let mut visited = Visited::default();
+        visited.insert(Point::new(0, 0));
         Self {
             moves: moves.to_vec(),
-            grid: vec![vec![false; w + 1]; h + 1],
-            head: origin,
-            tail: origin,
+            visited,
+            knots: vec![Point::new(0, 0); knots],
         }
     }
 
-    /// Given a move, move head and tail according to the following rules:
-    /// 1. Head cannot be farther than one cell away in any direction (up, down, left, right, diagonally)
-    /// 2. If tail is diagonal to head and head would move out of range,
-    ///    tail should end up in the space head was formerly occupying
+    fn head(&self) -> Point {
+        self.knots[0]
+    }
+
+    fn tail(&self) -> Point {
+        self.knots[self.knots.len() - 1]
+    }
+
+    /// Given a move, move the head one step at a time.
     fn move_rope(&mut self, m: &Move) {
         for _ in 0..m.dist {
-            self.head.move_direction(m.dir, 1);
-            let diff = self.tail.diff(&self.head);
-            if diff > 1 {
-                self.tail.move_direction(m.dir, 1);
-                match m.dir {
-                    Dir::U | Dir::D => self.tail.x = self.head.x,
-                    Dir::R | Dir::L => self.tail.y = self.head.y,
-                }
-            }
+            self.step(m.dir);
+        }
+    }
 
-            self.tail_visit();
+    /// Move the head a single cell, with every other knot following the one ahead of it (see
+    /// [`Point::follow`]).
+    fn step(&mut self, dir: Dir) {
+        self.knots[0].move_direction(dir, 1);
+        for k in 1..self.knots.len() {
+            let leader = self.knots[k - 1];
+            self.knots[k].follow(&leader);
         }
 
-        dbg!(&self);
+        self.visited.insert(self.tail());
     }
 
-    fn tail_visit(&mut self) {
-        let (x, y): (usize, usize) = (
-            self.tail.x.try_into().unwrap(),
-            self.tail.y.try_into().unwrap(),
-        );
-        if !self.grid[y][x] {
-            self.grid[y][x] = true;
-        }
+    /// Draw the part of the board in `viewport` the way the puzzle does, top row first.
+    ///
+    /// Knots are labelled `H` for the head and numbered from there, except that the tail of a rope
+    /// of two knots is `T`. Where knots overlap, the one nearest the head is drawn. Otherwise `s`
+    /// marks the start, `#` a cell the tail has visited and `.` any other cell.
+    pub fn render(&self, viewport: Viewport) -> String {
+        self.draw(viewport, |p| {
+            let k = self.knots.iter().position(|&k| k == p)?;
+            Some(match k {
+                0 => 'H',
+                1 if self.knots.len() == 2 => 'T',
+                k => char::from_digit(k as u32 % 36, 36).unwrap(),
+            })
+        })
+    }
+
+    /// Like [`RopeBoard::render`], but leaving out the knots to show just where the tail has been.
+    pub fn render_visited(&self, viewport: Viewport) -> String {
+        self.draw(viewport, |_| None)
     }
 
-    /// Return the dimensions of the grid and point of origin as indicated by the moves and their distances.
-    fn calc_dimensions_and_origin(moves: &Vec<Move>) -> ((usize, usize), Point) {
-        let (mut x, mut y): (i32, i32) = (0, 0);
-        let (mut max_x, mut max_y): (i32, i32) = (0, 0);
-        let (mut min_x, mut min_y): (i32, i32) = (0, 0);
+    /// Draw every cell in `viewport`, using `knot` for the cells it has a label for.
+    fn draw(&self, viewport: Viewport, knot: impl Fn(Point) -> Option<char>) -> String {
+        let (min, max) = self.bounds(viewport);
+        let mut frame = String::new();
+        for y in (min.y..=max.y).rev() {
+            for x in min.x..=max.x {
+                let p = Point::new(x, y);
+                frame.push(match knot(p) {
+                    Some(label) => label,
+                    None if p == Point::new(0, 0) => 's',
+                    None if self.visited.contains(p) => '#',
+                    None => '.',
+                });
+            }
+            if y > min.y {
+                frame.push('\n');
+            }
+        }
+        frame
+    }
 
-        for m in moves {
-            match m.dir {
-                Dir::U => y += m.dist as i32,
-                Dir::D => y -= m.dist as i32,
-                Dir::R => x += m.dist as i32,
-                Dir::L => x -= m.dist as i32,
+    /// Bottom left and top right corners of `viewport`.
+    fn bounds(&self, viewport: Viewport) -> (Point, Point) {
+        match viewport {
+            Viewport::Fit => self
+                .knots
+                .iter()
+                .fold(self.visited.bounds(), |(min, max), k| {
+                    (
+                        Point::new(min.x.min(k.x), min.y.min(k.y)),
+                        Point::new(max.x.max(k.x), max.y.max(k.y)),
+                    )
+                }),
+            Viewport::Fixed { min, max } => (min, max),
+            Viewport::Follow { width, height } => {
+                let min = Point::new(self.head().x - width / 2, self.head().y - height / 2);
+                (min, Point::new(min.x + width - 1, min.y + height - 1))
             }
-            max_x = cmp::max(x, max_x);
-            min_x = cmp::max(x, min_x);
-            max_y = cmp::max(y, max_y);
-            min_y = cmp::max(y, min_y);
         }
+    }
 
-        (
-            (
-                (max_x + min_x.abs() + 1) as usize,
-                (max_y + min_y.abs() + 1) as usize,
-            ),
-            Point::new(min_x.abs() + 1, min_y.abs() + 1),
-        )
+    /// Play every move, yielding a frame of `viewport` before the first step and after each one.
+    pub fn frames(&mut self, viewport: Viewport) -> impl Iterator<Item = String> + '_ {
+        let first = self.render(viewport);
+        let moves = self.moves.clone();
+        let steps = moves
+            .into_iter()
+            .flat_map(|m| iter::repeat_n(m.dir, m.dist));
+        iter::once(first).chain(steps.map(move |dir| {
+            self.step(dir);
+            self.render(viewport)
+        }))
     }
 
-    /// Move head and simulate tail movements, tracking tail visitations in grid
+    /// Play every move as an animation on a terminal, redrawing `viewport` in place on `out`
+    /// every `delay`.
+    pub fn replay(
+        &mut self,
+        viewport: Viewport,
+        delay: Duration,
+        out: &mut impl Write,
+    ) -> io::Result<()> {
+        for frame in self.frames(viewport) {
+            // clear the screen and move the cursor to the top left corner
+            writeln!(out, "\x1b[2J\x1b[H{}", frame)?;
+            out.flush()?;
+            thread::sleep(delay);
+        }
+        Ok(())
+    }
+
+    /// Move head and simulate tail movements, tracking tail visitations
     pub fn simulate_moves(&mut self) {
         self.moves.to_vec().iter().for_each(|m| {
             self.move_rope(m);
@@ -151,15 +286,12 @@ impl RopeBoard {
 
     /// Sum total of visited cells
     pub fn get_visited_cell_ct(&self) -> usize {
-        self.grid
-            .iter()
-            .map(|r| r.iter().filter(|c| **c).count())
-            .sum::<usize>()
+        self.visited.len()
     }
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-enum Dir {
+pub enum Dir {
     U,
     D,
     R,
@@ -167,7 +299,7 @@ enum Dir {
 }
 
 #[derive(Debug, PartialEq, Eq, Copy, Clone)]
-struct Move {
+pub struct Move {
     dist: usize,
     dir: Dir,
 }
@@ -178,41 +310,74 @@ impl Move {
     }
 }
 
-#[derive(Debug, PartialEq, Eq)]
-struct ParseMoveError;
-
 impl FromStr for Move {
-    type Err = ParseMoveError;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let p: Vec<&str> = s.split_whitespace().collect();
-        let n = p[1].parse::<usize>().unwrap();
-        Ok(match p[0] {
+        let (dir, n) = s
+            .split_once(' ')
+            .ok_or_else(|| ParseError::locate(s, &s[s.len()..], "a direction and distance"))?;
+        let n = n
+            .parse::<usize>()
+            .map_err(|_| ParseError::locate(s, n, "a distance"))?;
+        Ok(match dir {
             "U" => Move::new(n, Dir::U),
             "D" => Move::new(n, Dir::D),
             "L" => Move::new(n, Dir::L),
             "R" => Move::new(n, Dir::R),
-            _ => unreachable!(),
+            _ => return Err(ParseError::locate(s, dir, "one of U, D, L, R")),
         })
     }
 }
 
 #[aoc_generator(day9)]
-fn parse_input(input: &str) -> Vec<Move> {
+fn parse_input(input: &str) -> Result<Vec<Move>, ParseError> {
     input
         .trim()
         .lines()
-        .map(|l| l.parse::<Move>().unwrap())
-        .collect::<Vec<Move>>()
+        .enumerate()
+        .map(|(n, l)| l.parse::<Move>().map_err(|e| e.on_line(n + 1)))
+        .collect()
 }
 
 #[aoc(day9, part1)]
-fn solve_d09_pt1(moves: &Vec<Move>) -> usize {
-    let mut board = RopeBoard::new(moves);
+fn solve_d09_pt1(moves: &[Move]) -> usize {
+    let mut board = RopeBoard::new(moves, 2);
     board.simulate_moves();
     board.get_visited_cell_ct()
 }
 
+#[aoc(day9, part2)]
+fn solve_d09_pt2(moves: &[Move]) -> usize {
+    let mut board = RopeBoard::new(moves, 10);
+    board.simulate_moves();
+    board.get_visited_cell_ct()
+}
+
+pub struct Day09;
+
+impl Puzzle for Day09 {
+    const DAY: u32 = 9;
+
+    type Input = Vec<Move>;
+    type Output1 = usize;
+    type Output2 = usize;
+    type Error = Infallible;
+
+    fn parse(&self, input: &str) -> Result<Self::Input, ParseError> {
+        parse_input(input)
+    }
+
+    fn part1(&self, moves: &Self::Input) -> Result<usize, Infallible> {
+        Ok(solve_d09_pt1(moves))
+    }
+
+    fn part2(&self, moves: &Self::Input) -> Result<usize, Infallible> {
+        Ok(solve_d09_pt2(moves))
+    }
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
 
@@ -228,7 +393,7 @@ R 2";
 
     #[test]
     fn example_pt1() {
-        let input = parse_input(EXAMPLE_INPUT);
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
         let expect = 13;
         let actual = solve_d09_pt1(&input);
         assert_eq!(expect, actual);
@@ -236,9 +401,128 @@ R 2";
 
     #[test]
     fn solve_pt1() {
-        let input = parse_input(FILE_INPUT);
+        let input = parse_input(FILE_INPUT).unwrap();
         let expect = 42;
         let actual = solve_d09_pt1(&input);
         assert_eq!(expect, actual);
     }
+
+    #[test]
+    fn example_pt2() {
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        let expect = 1;
+        let actual = solve_d09_pt2(&input);
+        assert_eq!(expect, actual);
+    }
+
+    #[ignore]
+    #[test]
+    fn solve_pt2() {
+        let input = parse_input(FILE_INPUT).unwrap();
+        let expect = 42;
+        let actual = solve_d09_pt2(&input);
+        assert_eq!(expect, actual);
+    }
+
+    #[test]
+    fn larger_example_pt2() {
+        let input = parse_input("R 5\nU 8\nL 8\nD 3\nR 17\nD 10\nL 25\nU 20").unwrap();
+        assert_eq!(36, solve_d09_pt2(&input));
+    }
+
+    #[test]
+    fn negative_and_far() {
+        let input = parse_input("L 5\nD 5\nR 3").unwrap();
+        assert_eq!(11, solve_d09_pt1(&input));
+
+        let mut visited = Visited::default();
+        for p in [(-1, -1), (0, 0), (63, 64), (-64, 0), (0, 0)] {
+            visited.insert(Point::new(p.0, p.1));
+        }
+        assert_eq!(4, visited.len());
+        assert!(visited.contains(Point::new(-64, 0)));
+        assert!(!visited.contains(Point::new(64, 63)));
+        assert_eq!((Point::new(-64, -1), Point::new(63, 64)), visited.bounds());
+    }
+
+    #[test]
+    fn example_frames() {
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        let mut board = RopeBoard::new(&input, 2);
+        let viewport = Viewport::Fixed {
+            min: Point::new(0, 0),
+            max: Point::new(5, 4),
+        };
+        let frames = board.frames(viewport).collect::<Vec<String>>();
+        assert_eq!(25, frames.len());
+        assert_eq!("......\n......\n......\n......\nH.....", frames[0]);
+        assert_eq!("......\n......\n......\n......\nTH....", frames[1]);
+        assert_eq!("......\n......\n......\n....H.\ns##T..", frames[5]);
+        assert_eq!("..##..\n...##.\n.TH##.\n....#.\ns###..", frames[24]);
+        assert_eq!(
+            "..##..\n...##.\n.####.\n....#.\ns###..",
+            board.render_visited(viewport)
+        );
+    }
+
+    #[test]
+    fn render_ten_knots() {
+        let input = parse_input("R 5\nU 8").unwrap();
+        let mut board = RopeBoard::new(&input, 10);
+        board.simulate_moves();
+        assert_eq!(
+            "\
+.....H
+.....1
+.....2
+.....3
+....54
+...6..
+..7...
+.8....
+9.....",
+            board.to_string()
+        );
+        assert_eq!(
+            "...\n.H.\n.1.",
+            board.render(Viewport::Follow {
+                width: 3,
+                height: 3
+            })
+        );
+    }
+
+    #[test]
+    fn replay_frames() {
+        let input = parse_input("R 2").unwrap();
+        let mut out = vec![];
+        RopeBoard::new(&input, 2)
+            .replay(Viewport::Fit, Duration::ZERO, &mut out)
+            .unwrap();
+        let out = String::from_utf8(out).unwrap();
+        assert_eq!("\x1b[2J\x1b[HH\n\x1b[2J\x1b[HTH\n\x1b[2J\x1b[HsTH\n", out);
+    }
+
+    #[test]
+    fn follow_leader() {
+        let follow = |leader: (i64, i64)| {
+            let mut knot = Point::new(0, 0);
+            knot.follow(&Point::new(leader.0, leader.1));
+            (knot.x, knot.y)
+        };
+        assert_eq!((0, 0), follow((1, 1)));
+        assert_eq!((1, 0), follow((2, 0)));
+        assert_eq!((0, -1), follow((0, -2)));
+        assert_eq!((1, 1), follow((2, 1)));
+        // only possible with more than 2 knots, where a leader can itself move diagonally
+        assert_eq!((-1, 1), follow((-2, 2)));
+    }
+
+    #[test]
+    fn bad_move() {
+        let err = parse_input("R 4\nX 4").unwrap_err();
+        assert_eq!(ParseError::new(2, 1, "X", "one of U, D, L, R"), err);
+        let err = parse_input("R 4\nU\nL -3").unwrap_err();
+        assert_eq!(ParseError::new(2, 2, "", "a direction and distance"), err);
+    }
 }
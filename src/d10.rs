@@ -1,76 +1,353 @@
+use std::collections::HashMap;
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
+use std::io::{self, Write};
+use std::num::NonZeroUsize;
+use std::ops::{Index, IndexMut};
 use std::str::FromStr;
 
+use crate::error::ParseError;
+use crate::solution::Puzzle;
+
 #[aoc_generator(day10)]
-fn parse_input(input: &str) -> Vec<Ins> {
-    input.trim().lines().map(|l| l.parse()).flatten().collect()
+fn parse_input(input: &str) -> Result<Vec<Ins>, ParseError> {
+    input
+        .trim()
+        .lines()
+        .enumerate()
+        .map(|(n, l)| l.parse().map_err(|e: ParseError| e.on_line(n + 1)))
+        .collect()
 }
 
 #[aoc(day10, part1)]
-fn solve_d10_pt1(instructions: &Vec<Ins>) -> i32 {
+fn solve_d10_pt1(instructions: &[Ins]) -> Result<i64, CpuError> {
     let mut vm = Vm::new(instructions);
     let cycles_of_interest: &[usize] = &[20, 60, 100, 140, 180, 220];
-    vm.run();
-    vm.eng
-        .cycles
-        .iter()
-        .enumerate()
-        .filter(|(n, _)| cycles_of_interest.contains(&(n + 1)))
-        .zip(cycles_of_interest)
-        .fold(0, |acc: i32, ((_, v), &c)| acc + c as i32 * v)
+    let mut strength = 0;
+    for &c in cycles_of_interest {
+        match vm.run_until(c) {
+            Stop::Reached => strength += c as i64 * i64::from(vm.x()),
+            Stop::Fault(e) => return Err(e),
+            _ => break,
+        }
+    }
+    Ok(strength)
 }
 
 #[aoc(day10, part2)]
-fn solve_d10_pt2(instructions: &Vec<Ins>) -> String {
+fn solve_d10_pt2(instructions: &[Ins]) -> Result<String, CrtError> {
     let mut vm = Vm::new(instructions);
-    vm.run();
-    vm.out.print();
-    vm.out.to_string()
+    vm.run()?;
+    Ok(vm.crt().read()?)
+}
+
+/// The shape of the screen and the sprite drawn on it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Screen {
+    pub width: usize,
+    pub height: usize,
+    /// How many pixels wide the sprite is, centred on X, with any extra pixel on the right.
+    pub sprite: usize,
+    /// What to do with cycles after the last pixel.
+    pub overrun: Overrun,
+}
+
+impl Screen {
+    /// Whether the sprite, when X is `x`, covers column `col`.
+    pub fn covers(&self, x: i32, col: usize) -> bool {
+        let left = x - (self.sprite as i32 - 1) / 2;
+        (left..left + self.sprite as i32).contains(&(col as i32))
+    }
+}
+
+/// The 40x6 screen of the puzzle, with a 3 pixel sprite.
+impl Default for Screen {
+    fn default() -> Self {
+        Self {
+            width: 40,
+            height: 6,
+            sprite: 3,
+            overrun: Overrun::Error,
+        }
+    }
+}
+
+/// What the screen does with a program running for more cycles than it has pixels.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Overrun {
+    /// Refuse to draw anything.
+    Error,
+    /// Leave the extra cycles out.
+    Clip,
+    /// Go back to the top left and draw over what's there.
+    Wrap,
+}
+
+/// A program ran for more cycles than the screen has pixels, see [`Overrun::Error`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OverrunError {
+    /// How many cycles had run when it was noticed, which [`Vm::run`] does one past the last
+    /// pixel.
+    pub cycles: usize,
+    pub pixels: usize,
+}
+
+impl Display for OverrunError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "the program ran for {} cycles, but the screen only has {} pixels",
+            self.cycles, self.pixels
+        )
+    }
+}
+
+impl Error for OverrunError {}
+
+/// Why the letters on the screen couldn't be read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum CrtError {
+    Cpu(CpuError),
+    Overrun(OverrunError),
+    /// The program was still running after [`Vm::CYCLE_LIMIT`] cycles.
+    Runaway {
+        cycles: usize,
+    },
+    Ocr(OcrError),
+}
+
+impl Display for CrtError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            CrtError::Cpu(e) => write!(f, "{}", e),
+            CrtError::Overrun(e) => write!(f, "{}", e),
+            CrtError::Runaway { cycles } => {
+                write!(f, "the program was still running after {} cycles", cycles)
+            }
+            CrtError::Ocr(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl Error for CrtError {}
+
+impl From<CpuError> for CrtError {
+    fn from(e: CpuError) -> Self {
+        CrtError::Cpu(e)
+    }
+}
+
+impl From<OverrunError> for CrtError {
+    fn from(e: OverrunError) -> Self {
+        CrtError::Overrun(e)
+    }
+}
+
+impl From<OcrError> for CrtError {
+    fn from(e: OcrError) -> Self {
+        CrtError::Ocr(e)
+    }
+}
+
+/// Where the [`Vm`] should stop running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Breakpoint {
+    /// When the given cycle comes up.
+    Cycle(usize),
+    /// When the register changes to the value.
+    Reg(Reg, i32),
+}
+
+/// Why the [`Vm`] stopped running.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stop {
+    /// It got to the cycle it was asked to run until.
+    Reached,
+    Breakpoint(Breakpoint),
+    /// The program counter left the program.
+    Halted,
+    /// An instruction overflowed a register.
+    Fault(CpuError),
 }
 
 #[derive(Debug)]
-struct Vm {
+pub struct Vm {
     ins: Vec<Ins>,
     eng: Engine,
     out: Crt,
+    screen: Screen,
+    breakpoints: Vec<Breakpoint>,
 }
 
 impl Vm {
-    pub fn new(ins: &Vec<Ins>) -> Self {
+    /// How many cycles [`Vm::run`] lets a program run for before giving up on it halting.
+    pub const CYCLE_LIMIT: usize = 1 << 20;
+
+    pub fn new(ins: &[Ins]) -> Self {
+        Self::with_screen(ins, Screen::default())
+    }
+
+    pub fn with_screen(ins: &[Ins], screen: Screen) -> Self {
         Self {
             ins: ins.to_vec(),
             eng: Engine::new(),
-            out: Crt::new(),
+            out: Crt::new(&screen),
+            screen,
+            breakpoints: vec![],
         }
     }
 
-    pub fn run(&mut self) {
-        let engine = &mut self.eng;
-        self.ins.iter().for_each(|i| engine.eval(i));
-        self.render();
+    /// Run until the program counter leaves the program, then draw the screen. Doesn't stop for
+    /// breakpoints.
+    ///
+    /// A `jmp` may never let the program halt, so it gives up as soon as the screen overruns with
+    /// [`Overrun::Error`], or after [`Vm::CYCLE_LIMIT`] cycles otherwise.
+    pub fn run(&mut self) -> Result<(), CrtError> {
+        let pixels = self.screen.width * self.screen.height;
+        while self.step_cycle()?.is_some() {
+            let cycles = self.eng.cycles.len();
+            if self.screen.overrun == Overrun::Error && cycles > pixels {
+                return Err(OverrunError { cycles, pixels }.into());
+            }
+            if cycles > Self::CYCLE_LIMIT {
+                return Err(CrtError::Runaway { cycles });
+            }
+        }
+        Ok(self.render()?)
     }
 
-    pub fn render(&mut self) {
-        let display = &mut self.out;
-        let engine = &self.eng;
+    /// Run a single cycle, returning its number, or `None` if the program has halted. Fails if
+    /// the instruction finishing on it overflows a register.
+    pub fn step_cycle(&mut self) -> Result<Option<usize>, CpuError> {
+        self.eng.step(&self.ins)
+    }
 
-        engine.cycles.iter().enumerate().for_each(|(i, &c)| {
-            let (x, y) = (i % display.width, i / display.width);
-            if x.checked_sub(1).unwrap_or(1) <= (c as usize)
-                && (c as usize) <= x + 1
-                && y < display.height
-            {
-                display.draw(y, x);
+    /// Run the rest of the current instruction, returning it, or `None` if the program has
+    /// halted. Fails if it overflows a register.
+    pub fn step_instruction(&mut self) -> Result<Option<Ins>, CpuError> {
+        let Some(ins) = self.instruction() else {
+            return Ok(None);
+        };
+        while self.step_cycle()?.is_some() && self.eng.in_flight.is_some() {}
+        Ok(Some(ins))
+    }
+
+    /// Run until cycle `cycle` is under way, or a breakpoint or the end of the program gets in
+    /// the way. Doesn't go back if it's already past it.
+    pub fn run_until(&mut self, cycle: usize) -> Stop {
+        while self.cycle() < cycle {
+            if let Some(stop) = self.step_and_check() {
+                return stop;
+            }
+        }
+        Stop::Reached
+    }
+
+    /// Run until a breakpoint or the end of the program. Always runs at least one cycle, so
+    /// resuming from a breakpoint doesn't stop at it again straight away.
+    pub fn resume(&mut self) -> Stop {
+        loop {
+            if let Some(stop) = self.step_and_check() {
+                return stop;
             }
+        }
+    }
+
+    /// Run a cycle, returning why to stop after it, if at all.
+    fn step_and_check(&mut self) -> Option<Stop> {
+        let before = self.eng.cpu.clone();
+        match self.step_cycle() {
+            Err(e) => return Some(Stop::Fault(e)),
+            Ok(cycle) if cycle.is_none() || self.is_halted() => return Some(Stop::Halted),
+            Ok(_) => {}
+        }
+        self.breakpoints
+            .iter()
+            .find(|&&bp| match bp {
+                Breakpoint::Cycle(c) => self.cycle() == c,
+                Breakpoint::Reg(r, v) => self.reg(r) == v && before[r] != v,
+            })
+            .map(|&bp| Stop::Breakpoint(bp))
+    }
+
+    pub fn add_breakpoint(&mut self, bp: Breakpoint) {
+        self.breakpoints.push(bp);
+    }
+
+    pub fn clear_breakpoints(&mut self) {
+        self.breakpoints.clear();
+    }
+
+    /// The cycle under way, which an instruction's effect only applies at the end of.
+    pub fn cycle(&self) -> usize {
+        self.eng.cycle + 1
+    }
+
+    /// The value of X during the cycle under way.
+    pub fn x(&self) -> i32 {
+        self.reg(Reg::X)
+    }
+
+    pub fn reg(&self, reg: Reg) -> i32 {
+        self.eng.cpu[reg]
+    }
+
+    /// The instruction the cycle under way belongs to, `None` once the program has halted.
+    pub fn instruction(&self) -> Option<Ins> {
+        match self.eng.in_flight {
+            Some((ins, _)) => Some(ins),
+            None => self.ins.get(self.eng.cpu.pc).copied(),
+        }
+    }
+
+    /// How many of the current instruction's cycles have already passed.
+    pub fn elapsed(&self) -> usize {
+        self.eng.in_flight.map_or(0, |(_, done)| done)
+    }
+
+    /// The screen as last drawn by [`Vm::render`].
+    pub fn crt(&self) -> &Crt {
+        &self.out
+    }
+
+    pub fn is_halted(&self) -> bool {
+        self.instruction().is_none()
+    }
+
+    /// Draw every cycle so far on the screen, the pixel the beam is on lit when the sprite covers
+    /// it.
+    pub fn render(&mut self) -> Result<(), OverrunError> {
+        let screen = self.screen;
+        let pixels = screen.width * screen.height;
+        let cycles = &self.eng.cycles;
+        let shown = match screen.overrun {
+            Overrun::Error if cycles.len() > pixels => {
+                return Err(OverrunError {
+                    cycles: cycles.len(),
+                    pixels,
+                });
+            }
+            Overrun::Wrap if pixels > 0 => cycles.len(),
+            _ => cycles.len().min(pixels),
+        };
+
+        cycles[..shown].iter().enumerate().for_each(|(i, &x)| {
+            let (col, row) = (i % screen.width, i / screen.width % screen.height);
+            self.out.pixels[row][col] = screen.covers(x, col);
         });
-        println!();
+        Ok(())
     }
 }
 
 #[derive(Debug)]
 struct Engine {
+    /// How many cycles have finished
     cycle: usize,
+    /// The value of X during every cycle so far
     cycles: Vec<i32>,
-    x_reg: i32,
+    cpu: Cpu,
+    /// The instruction partway through running, and how many of its cycles have passed
+    in_flight: Option<(Ins, usize)>,
 }
 
 impl Engine {
@@ -78,48 +355,179 @@ impl Engine {
         Self {
             cycle: 0,
             cycles: vec![],
-            x_reg: 1,
+            cpu: Cpu::new(),
+            in_flight: None,
         }
     }
 
-    fn eval(&mut self, i: &Ins) {
-        match i {
-            Ins::AddX(x) => self.addx(x),
-            Ins::NoOp => self.noop(),
+    /// Run a cycle of the current instruction, or start on the next one in `program`, applying
+    /// the instruction's effect once its cycles are up. Returns the number of the cycle, or `None`
+    /// if the program counter has left the program.
+    fn step(&mut self, program: &[Ins]) -> Result<Option<usize>, CpuError> {
+        let (ins, done) = match self.in_flight.take() {
+            Some(in_flight) => in_flight,
+            None => match program.get(self.cpu.pc) {
+                Some(&ins) => (ins, 0),
+                None => return Ok(None),
+            },
+        };
+        self.cycle += 1;
+        self.cycles.push(self.cpu[Reg::X]);
+        if done + 1 < ins.op.cycles {
+            self.in_flight = Some((ins, done + 1));
+        } else {
+            self.cpu.pc += 1;
+            (ins.op.effect)(&mut self.cpu, ins.arg)?;
         }
+        Ok(Some(self.cycle))
     }
+}
 
-    fn addx(&mut self, x: &i32) {
-        self.advance_cycle(2);
-        self.x_reg += x;
+/// A register of the [`Cpu`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Reg {
+    /// The middle of the sprite, and what the signal strength is measured from.
+    X,
+    Y,
+}
+
+impl Reg {
+    pub const ALL: [Reg; 2] = [Reg::X, Reg::Y];
+}
+
+/// What instructions get to change.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cpu {
+    regs: [i32; Reg::ALL.len()],
+    /// Index of the instruction running, already moved on to the next one by the time its effect
+    /// applies
+    pub pc: usize,
+}
+
+impl Cpu {
+    fn new() -> Self {
+        Self {
+            regs: [1, 0],
+            pc: 0,
+        }
     }
 
-    fn noop(&mut self) {
-        self.advance_cycle(1);
+    /// Set `reg` to what `f` makes of its value, leaving it be if that overflows.
+    pub fn update(&mut self, reg: Reg, f: impl FnOnce(i32) -> Option<i32>) -> Result<(), CpuError> {
+        self[reg] = f(self[reg]).ok_or(CpuError { reg })?;
+        Ok(())
     }
+}
 
-    fn advance_cycle(&mut self, n: usize) {
-        (0..n).for_each(|n| {
-            self.cycle += 1;
-            self.cycles.push(self.x_reg);
-        });
+/// An instruction's effect overflowed a register, see [`Cpu::update`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CpuError {
+    pub reg: Reg,
+}
+
+impl Display for CpuError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "register {:?} overflowed", self.reg)
+    }
+}
+
+impl Error for CpuError {}
+
+impl Index<Reg> for Cpu {
+    type Output = i32;
+
+    fn index(&self, reg: Reg) -> &i32 {
+        &self.regs[reg as usize]
     }
 }
 
-struct Crt {
+impl IndexMut<Reg> for Cpu {
+    fn index_mut(&mut self, reg: Reg) -> &mut i32 {
+        &mut self.regs[reg as usize]
+    }
+}
+
+/// What follows an instruction's mnemonic.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Operand {
+    None,
+    /// A signed integer, e.g. `addx -5`.
+    Int,
+}
+
+/// Everything the CPU needs to know about an instruction.
+///
+/// Adding an instruction only takes a new `Opcode` listed in [`INSTRUCTION_SET`].
+#[derive(Debug)]
+pub struct Opcode {
+    pub mnemonic: &'static str,
+    pub operand: Operand,
+    /// How many cycles it takes before its effect applies.
+    pub cycles: usize,
+    /// What it does once its cycles are up, given its operand (0 when it has none).
+    pub effect: fn(&mut Cpu, i32) -> Result<(), CpuError>,
+}
+
+pub static NOOP: Opcode = Opcode {
+    mnemonic: "noop",
+    operand: Operand::None,
+    cycles: 1,
+    effect: |_, _| Ok(()),
+};
+
+pub static ADDX: Opcode = Opcode {
+    mnemonic: "addx",
+    operand: Operand::Int,
+    cycles: 2,
+    effect: |cpu, v| cpu.update(Reg::X, |x| x.checked_add(v)),
+};
+
+pub static ADDY: Opcode = Opcode {
+    mnemonic: "addy",
+    operand: Operand::Int,
+    cycles: 2,
+    effect: |cpu, v| cpu.update(Reg::Y, |y| y.checked_add(v)),
+};
+
+pub static MULX: Opcode = Opcode {
+    mnemonic: "mulx",
+    operand: Operand::Int,
+    cycles: 2,
+    effect: |cpu, v| cpu.update(Reg::X, |x| x.checked_mul(v)),
+};
+
+/// Jumps `v` instructions away from itself, halting when that's outside the program.
+pub static JMP: Opcode = Opcode {
+    mnemonic: "jmp",
+    operand: Operand::Int,
+    cycles: 1,
+    effect: |cpu, v| {
+        cpu.pc = (cpu.pc as i64 - 1 + v as i64)
+            .try_into()
+            .unwrap_or(usize::MAX);
+        Ok(())
+    },
+};
+
+/// Every instruction programs can use.
+pub static INSTRUCTION_SET: &[&Opcode] = &[&NOOP, &ADDX, &ADDY, &MULX, &JMP];
+
+/// What the screen shows, each pixel either lit or dark.
+///
+/// Besides the `#`/`.` text of [`Display`] it can be drawn as half blocks on a terminal, or
+/// exported as a PBM, PGM or PNG picture with lit pixels black on white.
+pub struct Crt {
     width: usize,
     height: usize,
     pixels: Vec<Vec<bool>>,
 }
 
 impl Crt {
-    fn new() -> Self {
-        let width = 40;
-        let height = 6;
+    fn new(screen: &Screen) -> Self {
         Self {
-            width,
-            height,
-            pixels: vec![vec![false; width]; height],
+            width: screen.width,
+            height: screen.height,
+            pixels: vec![vec![false; screen.width]; screen.height],
         }
     }
 
@@ -127,11 +535,217 @@ impl Crt {
         self.pixels[row][col] = true;
     }
 
-    fn print(&self) {
-        println!("{:?}", self);
+    /// Draw the screen with two rows of pixels to a line of text, using `▀`, `▄` and `█`, which
+    /// makes pixels about square on most terminals. With `ansi`, lit pixels are green on black,
+    /// like the real thing.
+    pub fn half_blocks(&self, ansi: bool) -> String {
+        let dark = vec![false; self.width];
+        self.pixels
+            .chunks(2)
+            .map(|rows| {
+                let (top, bottom) = (&rows[0], rows.get(1).unwrap_or(&dark));
+                let line = top
+                    .iter()
+                    .zip(bottom)
+                    .map(|pair| match pair {
+                        (true, true) => '█',
+                        (true, false) => '▀',
+                        (false, true) => '▄',
+                        (false, false) => ' ',
+                    })
+                    .collect::<String>();
+                match ansi {
+                    true => format!("\x1b[32;40m{}\x1b[0m", line),
+                    false => line,
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+
+    /// The rows of pixels, each pixel blown up to a `scale` by `scale` square.
+    fn scaled(&self, scale: NonZeroUsize) -> impl Iterator<Item = Vec<bool>> + '_ {
+        let scale = scale.get();
+        self.pixels.iter().flat_map(move |row| {
+            let row = row
+                .iter()
+                .flat_map(|&p| std::iter::repeat_n(p, scale))
+                .collect::<Vec<bool>>();
+            std::iter::repeat_n(row, scale)
+        })
+    }
+
+    /// Write the screen to `out` as a binary (P4) PBM, scaled up by `scale`.
+    pub fn write_pbm(&self, scale: NonZeroUsize, out: &mut impl Write) -> io::Result<()> {
+        let (width, height) = (self.width * scale.get(), self.height * scale.get());
+        write!(out, "P4\n{} {}\n", width, height)?;
+        for row in self.scaled(scale) {
+            // 8 pixels to a byte, the first one in the highest bit, and each row padded to a byte
+            let bytes = row
+                .chunks(8)
+                .map(|bits| {
+                    bits.iter()
+                        .enumerate()
+                        .fold(0u8, |byte, (i, &lit)| byte | (lit as u8) << (7 - i))
+                })
+                .collect::<Vec<u8>>();
+            out.write_all(&bytes)?;
+        }
+        Ok(())
+    }
+
+    /// Write the screen to `out` as a binary (P5) PGM, scaled up by `scale`.
+    pub fn write_pgm(&self, scale: NonZeroUsize, out: &mut impl Write) -> io::Result<()> {
+        let (width, height) = (self.width * scale.get(), self.height * scale.get());
+        write!(out, "P5\n{} {}\n255\n", width, height)?;
+        for row in self.scaled(scale) {
+            out.write_all(&grey(&row))?;
+        }
+        Ok(())
     }
+
+    /// Write the screen to `out` as an 8-bit greyscale PNG, scaled up by `scale`.
+    ///
+    /// The pixel data isn't compressed, which keeps this short and is plenty for a picture this
+    /// size.
+    pub fn write_png(&self, scale: NonZeroUsize, out: &mut impl Write) -> io::Result<()> {
+        let (width, height) = (self.width * scale.get(), self.height * scale.get());
+        let too_big = || io::Error::new(io::ErrorKind::InvalidInput, "too big for a PNG");
+        let mut header = vec![];
+        header.extend(u32::try_from(width).map_err(|_| too_big())?.to_be_bytes());
+        header.extend(u32::try_from(height).map_err(|_| too_big())?.to_be_bytes());
+        // bit depth 8, greyscale, then the only compression, filtering and interlacing there are
+        header.extend([8, 0, 0, 0, 0]);
+
+        // every row starts with the filter it uses, none
+        let raw = self
+            .scaled(scale)
+            .flat_map(|row| std::iter::once(0).chain(grey(&row)))
+            .collect::<Vec<u8>>();
+        // a zlib stream of stored deflate blocks, the last one flagged as such
+        let mut data = vec![0x78, 0x01];
+        let blocks = raw.chunks(u16::MAX as usize).collect::<Vec<&[u8]>>();
+        for (i, block) in blocks.iter().enumerate() {
+            let len = block.len() as u16;
+            data.push((i + 1 == blocks.len()) as u8);
+            data.extend(len.to_le_bytes());
+            data.extend((!len).to_le_bytes());
+            data.extend(*block);
+        }
+        if blocks.is_empty() {
+            data.extend([1, 0, 0, 0xff, 0xff]);
+        }
+        data.extend(adler32(&raw).to_be_bytes());
+
+        out.write_all(b"\x89PNG\r\n\x1a\n")?;
+        for (kind, body) in [(b"IHDR", &header), (b"IDAT", &data), (b"IEND", &vec![])] {
+            out.write_all(&(body.len() as u32).to_be_bytes())?;
+            out.write_all(kind)?;
+            out.write_all(body)?;
+            let crc = crc32(kind.iter().chain(body.iter()));
+            out.write_all(&crc.to_be_bytes())?;
+        }
+        Ok(())
+    }
+
+    /// Read the capital letters drawn on the screen, which are 4 pixels wide with a column
+    /// between each of them.
+    pub fn read(&self) -> Result<String, OcrError> {
+        (0..(self.width + 1) / GLYPH_SPACING)
+            .map(|index| {
+                let left = index * GLYPH_SPACING;
+                let glyph = self
+                    .pixels
+                    .iter()
+                    .map(|row| {
+                        row[left..left + GLYPH_WIDTH]
+                            .iter()
+                            .map(|&p| if p { '#' } else { '.' })
+                            .collect::<String>()
+                    })
+                    .collect::<Vec<String>>();
+                FONT.iter()
+                    .find(|(_, rows)| rows[..] == glyph[..])
+                    .map(|&(letter, _)| letter)
+                    .ok_or_else(|| OcrError {
+                        index,
+                        glyph: glyph.join("\n"),
+                    })
+            })
+            .collect()
+    }
+}
+
+/// A row of pixels as bytes of a greyscale picture, lit pixels black on white.
+fn grey(row: &[bool]) -> Vec<u8> {
+    row.iter().map(|&lit| if lit { 0 } else { 255 }).collect()
+}
+
+/// The checksum ending a zlib stream.
+fn adler32(bytes: &[u8]) -> u32 {
+    let (a, b) = bytes.iter().fold((1u32, 0u32), |(a, b), &byte| {
+        let a = (a + byte as u32) % 65521;
+        (a, (b + a) % 65521)
+    });
+    b << 16 | a
+}
+
+/// The checksum ending a PNG chunk, of its type and data.
+fn crc32<'a>(bytes: impl Iterator<Item = &'a u8>) -> u32 {
+    !bytes.fold(!0u32, |crc, &byte| {
+        (0..8).fold(crc ^ byte as u32, |crc, _| match crc & 1 {
+            1 => crc >> 1 ^ 0xedb8_8320,
+            _ => crc >> 1,
+        })
+    })
 }
 
+const GLYPH_WIDTH: usize = 4;
+/// How far apart the left edges of neighbouring letters are.
+const GLYPH_SPACING: usize = GLYPH_WIDTH + 1;
+
+/// The letters puzzles spell out, as far as they're known.
+const FONT: &[(char, [&str; 6])] = &[
+    ('A', [".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', ["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', [".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', ["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', ["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', [".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', ["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', [".###", "..#.", "..#.", "..#.", "..#.", ".###"]),
+    ('J', ["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', ["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', ["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', [".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', ["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', ["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', [".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', ["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Z', ["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// A glyph on the screen that isn't a letter of the [`FONT`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OcrError {
+    /// The 0-based position of the glyph, counting from the left.
+    pub index: usize,
+    /// The glyph as drawn, one line per row.
+    pub glyph: String,
+}
+
+impl Display for OcrError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "glyph {} is not a letter I know:\n{}",
+            self.index, self.glyph
+        )
+    }
+}
+
+impl Error for OcrError {}
+
 impl std::fmt::Display for Crt {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let mut output = String::new();
@@ -151,90 +765,913 @@ impl std::fmt::Display for Crt {
     }
 }
 
+impl FromStr for Crt {
+    type Err = ParseError;
+
+    /// Read a picture of the default [`Screen`] as [`Display`] draws it, `#` for lit pixels and
+    /// `.` for dark ones.
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut crt = Crt::new(&Screen::default());
+        let mut lines = s.lines();
+        for row in 0..crt.height {
+            let line = lines
+                .next()
+                .ok_or_else(|| ParseError::locate(s, &s[s.len()..], "another row of pixels"))?;
+            let mut chars = line.char_indices();
+            for col in 0..crt.width {
+                match chars.next() {
+                    Some((_, '#')) => crt.draw(row, col),
+                    Some((_, '.')) => {}
+                    Some((i, _)) => return Err(ParseError::locate(s, &line[i..], "`#` or `.`")),
+                    None => {
+                        let expected = format!("{} pixels", crt.width);
+                        return Err(ParseError::locate(s, &line[line.len()..], &expected));
+                    }
+                }
+            }
+            if let Some((i, _)) = chars.next() {
+                return Err(ParseError::locate(s, &line[i..], "the end of the row"));
+            }
+        }
+        match lines.next() {
+            Some(extra) => Err(ParseError::locate(s, extra, "the end of the picture")),
+            None => Ok(crt),
+        }
+    }
+}
+
 impl std::fmt::Debug for Crt {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         write!(f, "{}", self)
     }
 }
 
-#[derive(Debug, Copy, Clone)]
-enum Ins {
-    AddX(i32),
-    NoOp,
+#[derive(Copy, Clone)]
+pub struct Ins {
+    pub op: &'static Opcode,
+    /// The operand, 0 for instructions without one.
+    pub arg: i32,
 }
 
-#[derive(Debug)]
-struct ParseInsError;
+impl Ins {
+    pub fn new(op: &'static Opcode, arg: i32) -> Self {
+        Self { op, arg }
+    }
+
+    pub fn noop() -> Self {
+        Self::new(&NOOP, 0)
+    }
+
+    pub fn addx(v: i32) -> Self {
+        Self::new(&ADDX, v)
+    }
+}
+
+impl PartialEq for Ins {
+    fn eq(&self, other: &Self) -> bool {
+        self.op.mnemonic == other.op.mnemonic && self.arg == other.arg
+    }
+}
+
+impl Eq for Ins {}
+
+/// Writes the instruction the way programs do, e.g. `addx -5`.
+impl Display for Ins {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self.op.operand {
+            Operand::None => write!(f, "{}", self.op.mnemonic),
+            Operand::Int => write!(f, "{} {}", self.op.mnemonic, self.arg),
+        }
+    }
+}
+
+impl Debug for Ins {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(f, "Ins({})", self)
+    }
+}
 
 impl FromStr for Ins {
-    type Err = ParseInsError;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let parts = s.split_whitespace().collect::<Vec<&str>>();
-        Ok(match parts[0] {
-            "addx" => Ins::AddX(parts[1].parse::<i32>().unwrap()),
-            "noop" => Ins::NoOp,
-            _ => unreachable!(),
+        let (mnemonic, arg) = s.split_once(' ').unwrap_or((s, &s[s.len()..]));
+        let op = opcode(s, mnemonic)?;
+        let arg = match op.operand {
+            Operand::None if arg.is_empty() => 0,
+            Operand::None => return Err(ParseError::locate(s, arg, "no operand")),
+            Operand::Int => arg
+                .parse::<i32>()
+                .map_err(|_| ParseError::locate(s, arg, "an integer operand"))?,
+        };
+        Ok(Ins::new(op, arg))
+    }
+}
+
+/// Look up the instruction called `mnemonic`, a part of `src`.
+fn opcode(src: &str, mnemonic: &str) -> Result<&'static Opcode, ParseError> {
+    INSTRUCTION_SET
+        .iter()
+        .find(|op| op.mnemonic == mnemonic)
+        .copied()
+        .ok_or_else(|| {
+            let known = INSTRUCTION_SET
+                .iter()
+                .map(|op| format!("`{}`", op.mnemonic))
+                .collect::<Vec<String>>();
+            ParseError::locate(src, mnemonic, &format!("one of {}", known.join(", ")))
         })
+}
+
+/// How deep macros can go on calling each other, so one calling itself is an error rather than a
+/// stack overflow.
+const MACRO_DEPTH: usize = 32;
+
+/// A macro definition, as tokens of the source it was defined in.
+struct Macro<'a> {
+    params: Vec<&'a str>,
+    /// Tokens of each line of the body, with where the line ends.
+    body: Vec<(Vec<&'a str>, &'a str)>,
+}
+
+/// A line of a program once macros are expanded.
+enum Stmt<'a> {
+    Label(&'a str),
+    Ins {
+        mnemonic: &'a str,
+        operand: Option<&'a str>,
+        /// Where the line ends, to point at when the operand is missing.
+        end: &'a str,
+    },
+}
+
+/// Assemble a program, where each line holds any of
+/// - a label, `name:`, standing for the offset from the instruction using it to the one after
+///   the label, which is what `jmp` expects
+/// - an instruction, `mnemonic [operand]`, the operand being an integer, a constant or a label,
+///   optionally negated with `-`
+/// - a comment, from `;` to the end of the line
+/// - a constant, `.const NAME value`
+/// - a macro, `.macro name [params...]` and its body up to `.endm`, used like an instruction
+///   with its arguments replacing its parameters wherever they appear in the body
+///
+/// Labels and constants can be used anywhere, macros only after they're defined.
+pub fn assemble(src: &str) -> Result<Vec<Ins>, ParseError> {
+    let mut lines = src.lines().map(|line| {
+        let code = line.split(';').next().unwrap_or(line);
+        (
+            code.split_whitespace().collect::<Vec<&str>>(),
+            &code[code.len()..],
+        )
+    });
+    let mut macros = HashMap::new();
+    let mut consts = HashMap::new();
+    let mut stmts = vec![];
+    while let Some((tokens, end)) = lines.next() {
+        match tokens[..] {
+            [] => {}
+            [".macro", name, ref params @ ..] => {
+                if macros.contains_key(name) || opcode(src, name).is_ok() {
+                    return Err(ParseError::locate(src, name, "a new macro name"));
+                }
+                let mut body = vec![];
+                loop {
+                    match lines.next() {
+                        Some((tokens, _)) if tokens[..] == [".endm"] => break,
+                        Some(line) => body.push(line),
+                        None => return Err(ParseError::locate(src, end, "a matching `.endm`")),
+                    }
+                }
+                let params = params.to_vec();
+                macros.insert(name, Macro { params, body });
+            }
+            [".macro"] => return Err(ParseError::locate(src, end, "a macro name")),
+            [".const", name, value] => {
+                let value = value
+                    .parse::<i32>()
+                    .map_err(|_| ParseError::locate(src, value, "an integer"))?;
+                if consts.insert(name, value).is_some() {
+                    return Err(ParseError::locate(src, name, "a new constant name"));
+                }
+            }
+            [".const", ..] => {
+                return Err(ParseError::locate(src, end, "a constant name and value"));
+            }
+            _ => statement(src, &tokens, end, &macros, &mut stmts, 0)?,
+        }
+    }
+
+    let mut labels = HashMap::new();
+    let mut index = 0;
+    for stmt in &stmts {
+        match *stmt {
+            Stmt::Label(name) if labels.insert(name, index).is_some() => {
+                return Err(ParseError::locate(src, name, "a new label name"));
+            }
+            Stmt::Label(_) => {}
+            Stmt::Ins { .. } => index += 1,
+        }
+    }
+
+    let value = |token: &str, index: usize| {
+        let (sign, name) = match token.strip_prefix('-') {
+            Some(name) => (-1, name),
+            None => (1, token),
+        };
+        if let Ok(n) = token.parse::<i32>() {
+            Ok(n)
+        } else if let Some(&n) = consts.get(name) {
+            Ok(sign * n)
+        } else if let Some(&target) = labels.get(name) {
+            Ok(sign * (target - index as i32))
+        } else {
+            Err(ParseError::locate(
+                src,
+                token,
+                "an integer, constant or label",
+            ))
+        }
+    };
+    stmts
+        .iter()
+        .filter_map(|stmt| match *stmt {
+            Stmt::Label(_) => None,
+            Stmt::Ins {
+                mnemonic,
+                operand,
+                end,
+            } => Some((mnemonic, operand, end)),
+        })
+        .enumerate()
+        .map(|(index, (mnemonic, operand, end))| {
+            let op = opcode(src, mnemonic)?;
+            let arg = match (op.operand, operand) {
+                (Operand::None, None) => 0,
+                (Operand::None, Some(t)) => return Err(ParseError::locate(src, t, "no operand")),
+                (Operand::Int, None) => {
+                    return Err(ParseError::locate(src, end, "an integer operand"));
+                }
+                (Operand::Int, Some(t)) => value(t, index)?,
+            };
+            Ok(Ins::new(op, arg))
+        })
+        .collect()
+}
+
+/// Turn the tokens of a line, which ends at `end`, into statements, expanding any macros.
+fn statement<'a>(
+    src: &str,
+    tokens: &[&'a str],
+    end: &'a str,
+    macros: &HashMap<&'a str, Macro<'a>>,
+    out: &mut Vec<Stmt<'a>>,
+    depth: usize,
+) -> Result<(), ParseError> {
+    match *tokens {
+        [] => {}
+        [label, ref rest @ ..] if label.ends_with(':') => {
+            let name = &label[..label.len() - 1];
+            if name.is_empty() || name.starts_with('-') {
+                return Err(ParseError::locate(src, label, "a label name"));
+            }
+            out.push(Stmt::Label(name));
+            statement(src, rest, end, macros, out, depth)?;
+        }
+        [name, ref args @ ..] if macros.contains_key(name) => {
+            let m = &macros[name];
+            if args.len() != m.params.len() {
+                let expected = format!("{} arguments to `{}`", m.params.len(), name);
+                return Err(ParseError::locate(src, name, &expected));
+            }
+            if depth == MACRO_DEPTH {
+                return Err(ParseError::locate(
+                    src,
+                    name,
+                    "a macro that doesn't call itself",
+                ));
+            }
+            for (line, end) in &m.body {
+                let line = line
+                    .iter()
+                    .map(|&t| m.params.iter().position(|&p| p == t).map_or(t, |i| args[i]))
+                    .collect::<Vec<&str>>();
+                statement(src, &line, end, macros, out, depth + 1)?;
+            }
+        }
+        [mnemonic] => out.push(Stmt::Ins {
+            mnemonic,
+            operand: None,
+            end,
+        }),
+        [mnemonic, operand] => out.push(Stmt::Ins {
+            mnemonic,
+            operand: Some(operand),
+            end,
+        }),
+        [_, _, extra, ..] => return Err(ParseError::locate(src, extra, "the end of the line")),
+    }
+    Ok(())
+}
+
+/// Write `program` out in a form [`assemble`] reads back in, noting which cycles each instruction
+/// runs during when the program runs straight through from the start.
+pub fn disassemble(program: &[Ins]) -> String {
+    let mut cycle = 1;
+    let mut out = String::new();
+    for ins in program {
+        let last = cycle + ins.op.cycles.max(1) - 1;
+        let cycles = match last == cycle {
+            true => format!("cycle {}", cycle),
+            false => format!("cycles {}-{}", cycle, last),
+        };
+        out += &format!("{:<12}; {}\n", ins.to_string(), cycles);
+        cycle = last + 1;
+    }
+    out
+}
+
+/// Why [`synthesize`] couldn't come up with a program.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SynthError {
+    /// The picture isn't one the screen can show.
+    Malformed(ParseError),
+    /// No program draws the picture, every one of them getting the pixel at `row`, `col` (or one
+    /// before it) wrong.
+    Unreachable { row: usize, col: usize },
+}
+
+impl Display for SynthError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            SynthError::Malformed(e) => write!(f, "{}", e),
+            SynthError::Unreachable { row, col } => write!(
+                f,
+                "no program can draw the picture, pixel {} of row {} can't be right",
+                col, row
+            ),
+        }
     }
 }
 
+impl Error for SynthError {}
+
+/// How a [`synthesize`]d program gets to the start of a cycle with some value of X.
+#[derive(Clone, Copy)]
+struct Path {
+    len: usize,
+    /// The cycle and X the last instruction started on, and the instruction.
+    last: Option<(usize, i32, Ins)>,
+}
+
+/// Come up with one of the shortest programs drawing `image` on the default [`Screen`], written the
+/// way the screen shows it (see [`Crt`]'s `Display`).
+///
+/// X only ever changes at the end of an `addx`, so the program is a run of values of X held for
+/// at least 2 cycles each (bar the last), each lighting the pixels around it. Only `noop` and
+/// `addx` are needed: `mulx` does nothing `addx` can't, and `jmp` can only skip over
+/// instructions or loop forever. The program halts as soon as the rest of the screen is dark, and
+/// never runs past the end of it.
+pub fn synthesize(image: &str) -> Result<Vec<Ins>, SynthError> {
+    let screen = Screen::default();
+    let target = image.parse::<Crt>().map_err(SynthError::Malformed)?;
+    let (width, cycles) = (screen.width, screen.width * screen.height);
+    let lit = |t: usize| target.pixels[t / width][t % width];
+    let draws = |t: usize, x: i32| lit(t) == screen.covers(x, t % width);
+    // any X far enough past the edges of the screen draws nothing, so one either side of it is
+    // enough
+    let margin = screen.sprite as i32;
+    let xs = -margin..=width as i32 + margin;
+    let slot = |x: i32| (x + margin) as usize;
+    let dark_from = (0..cycles).rev().find(|&t| lit(t)).map_or(0, |t| t + 1);
+
+    // the shortest way to the start of each cycle with each X, getting every pixel before it
+    // right
+    let mut paths = vec![vec![None::<Path>; xs.clone().count()]; cycles + 1];
+    paths[0][slot(1)] = Some(Path { len: 0, last: None });
+    for t in 0..dark_from {
+        for x in xs.clone() {
+            let Some(path) = paths[t][slot(x)] else {
+                continue;
+            };
+            if !draws(t, x) {
+                continue;
+            }
+            let mut offer = |t: usize, to: i32, ins: Ins| {
+                let next: &mut Option<Path> = &mut paths[t][slot(to)];
+                if next.is_none_or(|p| p.len > path.len + 1) {
+                    *next = Some(Path {
+                        len: path.len + 1,
+                        last: Some((t - ins.op.cycles, x, ins)),
+                    });
+                }
+            };
+            offer(t + 1, x, Ins::noop());
+            if t + 1 < cycles && draws(t + 1, x) {
+                for to in xs.clone() {
+                    offer(t + 2, to, Ins::addx(to - x));
+                }
+            }
+        }
+    }
+
+    let end = (dark_from..=cycles)
+        .flat_map(|t| xs.clone().map(move |x| (t, x)))
+        .filter_map(|(t, x)| Some((paths[t][slot(x)]?.len, t, x)))
+        .min();
+    let Some((_, mut t, mut x)) = end else {
+        let stuck = (0..dark_from)
+            .rev()
+            .find(|&t| paths[t].iter().any(Option::is_some))
+            .unwrap_or(0);
+        return Err(SynthError::Unreachable {
+            row: stuck / width,
+            col: stuck % width,
+        });
+    };
+    let mut program = vec![];
+    while let Some((from, from_x, ins)) = paths[t][slot(x)].and_then(|p| p.last) {
+        program.push(ins);
+        (t, x) = (from, from_x);
+    }
+    program.reverse();
+    Ok(program)
+}
+
+pub struct Day10;
+
+impl Puzzle for Day10 {
+    const DAY: u32 = 10;
+
+    type Input = Vec<Ins>;
+    type Output1 = i64;
+    type Output2 = String;
+    type Error = CrtError;
+
+    fn parse(&self, input: &str) -> Result<Self::Input, ParseError> {
+        parse_input(input)
+    }
+
+    fn part1(&self, instructions: &Self::Input) -> Result<i64, CrtError> {
+        Ok(solve_d10_pt1(instructions)?)
+    }
+
+    fn part2(&self, instructions: &Self::Input) -> Result<String, CrtError> {
+        solve_d10_pt2(instructions)
+    }
+}
+
+#[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     static SMALL_EXAMPLE_INPUT: &str = "noop
 addx 3
 addx -5";
     static EXAMPLE_INPUT: &str = include_str!("../input/2022/d10-example.txt");
     static FILE_INPUT: &str = include_str!("../input/2022/day10.txt");
+    static LETTERS: &str = "\
+###..###....##.#....####.#..#.#....###..
+#..#.#..#....#.#....#....#..#.#....#..#.
+###..#..#....#.#....###..#..#.#....#..#.
+#..#.###.....#.#....#....#..#.#....###..
+#..#.#.#..#..#.#....#....#..#.#....#....
+###..#..#..##..####.#.....##..####.#....";
 
     #[test]
     fn small_example_pt1() {
-        let input = parse_input(SMALL_EXAMPLE_INPUT);
-        let expect = 0;
+        let input = parse_input(SMALL_EXAMPLE_INPUT).unwrap();
+        let expect = Ok(0);
         let actual = solve_d10_pt1(&input);
         assert_eq!(expect, actual);
     }
 
     #[test]
     fn example_pt1() {
-        let input = parse_input(EXAMPLE_INPUT);
-        let expect = 13140;
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        let expect = Ok(13140);
         let actual = solve_d10_pt1(&input);
         assert_eq!(expect, actual);
     }
 
     #[test]
     fn solve_pt1() {
-        let input = parse_input(FILE_INPUT);
-        let expect = 12980;
+        let input = parse_input(FILE_INPUT).unwrap();
+        let expect = Ok(12980);
         let actual = solve_d10_pt1(&input);
         assert_eq!(expect, actual);
     }
 
     #[test]
     fn example_pt2() {
-        let input = parse_input(EXAMPLE_INPUT);
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
         let expect = "##..##..##..##..##..##..##..##..##..##..
 ###...###...###...###...###...###...###.
 ####....####....####....####....####....
 #####.....#####.....#####.....#####.....
 ######......######......######......####
 #######.......#######.......#######.....";
-        let actual = solve_d10_pt2(&input);
-        assert_eq!(expect, actual);
+        let mut vm = Vm::new(&input);
+        vm.run().unwrap();
+        assert_eq!(expect, vm.out.to_string());
+        assert_eq!(
+            Err(CrtError::Ocr(OcrError {
+                index: 0,
+                glyph: "##..\n###.\n####\n####\n####\n####".to_string()
+            })),
+            solve_d10_pt2(&input)
+        );
     }
 
     #[test]
     fn solve_pt2() {
-        let input = parse_input(FILE_INPUT);
-        let expect = "###..###....##.#....####.#..#.#....###..
-...#.#..#....#.#....#....#..#.#....#..#.
-.##..#..#....#.#....###..#..#.#....#..#.
-...#.###.....#.#....#....#..#.#....###..
-...#.#.#..#..#.#....#....#..#.#....#....
-###..#..#..##..####.#.....##..####.#....";
-        let actual = solve_d10_pt2(&input);
+        let input = parse_input(FILE_INPUT).unwrap();
+        let expect = "BRJLFULP";
+        let actual = solve_d10_pt2(&input).unwrap();
         assert_eq!(expect, actual);
     }
+
+    #[test]
+    fn read_letters() {
+        let mut crt = Crt::new(&Screen::default());
+        for (y, row) in LETTERS.lines().enumerate() {
+            for (x, _) in row.match_indices('#') {
+                crt.draw(y, x);
+            }
+        }
+        assert_eq!(Ok("BRJLFULP".to_string()), crt.read());
+    }
+
+    #[test]
+    fn bad_instruction() {
+        let err = parse_input("noop\naddx 3\nsubx 5").unwrap_err();
+        assert_eq!(
+            ParseError::new(3, 1, "subx", "one of `noop`, `addx`, `addy`, `mulx`, `jmp`"),
+            err
+        );
+        let err = parse_input("addx three").unwrap_err();
+        assert_eq!(ParseError::new(1, 6, "three", "an integer operand"), err);
+    }
+
+    #[test]
+    fn extended_instructions() {
+        let input = parse_input("addy 5\nmulx 3\njmp 2\naddx 100\nnoop\njmp -6").unwrap();
+        assert_eq!(Ins::new(&JMP, -6), input[5]);
+        assert_eq!("mulx 3", input[1].to_string());
+        let mut vm = Vm::new(&input);
+        vm.run().unwrap();
+        assert_eq!(vec![1, 1, 1, 1, 3, 3, 3], vm.eng.cycles);
+        assert_eq!(3, vm.eng.cpu[Reg::X]);
+        assert_eq!(5, vm.eng.cpu[Reg::Y]);
+    }
+
+    #[test]
+    fn overflow() {
+        let fault = CpuError { reg: Reg::X };
+        let input = parse_input("mulx 100000\nmulx 100000\nmulx 100000").unwrap();
+        assert_eq!(Err(CrtError::Cpu(fault)), solve_d10_pt2(&input));
+        let input = parse_input("addx 2147483647\naddx 1").unwrap();
+        assert_eq!(Err(fault), solve_d10_pt1(&input));
+        let input = parse_input("addx 2147483646\naddx 1").unwrap();
+        let mut vm = Vm::new(&input);
+        assert_eq!(Stop::Fault(fault), vm.run_until(10));
+        assert_eq!((5, i32::MAX), (vm.cycle(), vm.x()));
+        let input = parse_input("addy -2147483648\naddy -1").unwrap();
+        let mut vm = Vm::new(&input);
+        assert_eq!(Stop::Fault(CpuError { reg: Reg::Y }), vm.run_until(10));
+    }
+
+    #[test]
+    fn x_during_cycle() {
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        let mut vm = Vm::new(&input);
+        assert_eq!(Stop::Reached, vm.run_until(20));
+        assert_eq!((20, 21), (vm.cycle(), vm.x()));
+        assert_eq!(Stop::Reached, vm.run_until(20));
+        assert_eq!(Stop::Reached, vm.run_until(220));
+        assert_eq!((220, 18), (vm.cycle(), vm.x()));
+        assert_eq!(Stop::Halted, vm.run_until(500));
+        assert!(vm.is_halted());
+    }
+
+    #[test]
+    fn step_through() {
+        let input = parse_input(SMALL_EXAMPLE_INPUT).unwrap();
+        let mut vm = Vm::new(&input);
+        assert_eq!(Ok(Some(Ins::noop())), vm.step_instruction());
+        assert_eq!(Ok(Some(2)), vm.step_cycle());
+        assert_eq!(Some(Ins::addx(3)), vm.instruction());
+        assert_eq!((3, 1, 1), (vm.cycle(), vm.elapsed(), vm.x()));
+        assert_eq!(Ok(Some(Ins::addx(3))), vm.step_instruction());
+        assert_eq!((4, 0, 4), (vm.cycle(), vm.elapsed(), vm.x()));
+        assert_eq!(Ok(Some(Ins::addx(-5))), vm.step_instruction());
+        assert_eq!(None, vm.instruction());
+        assert_eq!(Ok(None), vm.step_cycle());
+        assert_eq!(vec![1, 1, 1, 4, 4], vm.eng.cycles);
+    }
+
+    #[test]
+    fn breakpoints() {
+        let input = parse_input(SMALL_EXAMPLE_INPUT).unwrap();
+        let mut vm = Vm::new(&input);
+        vm.add_breakpoint(Breakpoint::Reg(Reg::X, 4));
+        vm.add_breakpoint(Breakpoint::Cycle(5));
+        let reg = Stop::Breakpoint(Breakpoint::Reg(Reg::X, 4));
+        assert_eq!(reg, vm.resume());
+        assert_eq!(4, vm.cycle());
+        // X staying 4 doesn't count as hitting the breakpoint again
+        assert_eq!(Stop::Breakpoint(Breakpoint::Cycle(5)), vm.resume());
+        assert_eq!(Stop::Halted, vm.resume());
+
+        let mut vm = Vm::new(&input);
+        vm.add_breakpoint(Breakpoint::Reg(Reg::X, 4));
+        assert_eq!(reg, vm.run_until(5));
+        vm.clear_breakpoints();
+        assert_eq!(Stop::Reached, vm.run_until(5));
+    }
+
+    #[test]
+    fn assemble_program() {
+        let src = "\
+.const STEP 3      ; how far to move each time
+.macro shift n
+    addx n
+    noop
+.endm
+.macro twice n
+    shift n
+    shift n
+.endm
+
+start: noop
+loop:
+    twice STEP
+    addx -STEP
+    jmp -done
+    jmp loop
+done:";
+        let expect = vec![
+            Ins::noop(),
+            Ins::addx(3),
+            Ins::noop(),
+            Ins::addx(3),
+            Ins::noop(),
+            Ins::addx(-3),
+            Ins::new(&JMP, -2),
+            Ins::new(&JMP, -6),
+        ];
+        assert_eq!(Ok(expect), assemble(src));
+    }
+
+    #[test]
+    fn disassemble_round_trip() {
+        let input = parse_input(SMALL_EXAMPLE_INPUT).unwrap();
+        let expect = "\
+noop        ; cycle 1
+addx 3      ; cycles 2-3
+addx -5     ; cycles 4-5
+";
+        assert_eq!(expect, disassemble(&input));
+
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        assert_eq!(Ok(input.clone()), assemble(&disassemble(&input)));
+    }
+
+    #[test]
+    fn bad_assembly() {
+        let err = |src| assemble(src).unwrap_err();
+        assert_eq!(
+            ParseError::new(2, 5, "nowhere", "an integer, constant or label"),
+            err("noop\njmp nowhere")
+        );
+        assert_eq!(
+            ParseError::new(2, 1, "a", "a new label name"),
+            err("a: noop\na:")
+        );
+        assert_eq!(
+            ParseError::new(3, 1, "w", "1 arguments to `w`"),
+            err(".macro w n\n.endm\nw")
+        );
+        assert_eq!(
+            ParseError::new(1, 11, "", "a matching `.endm`"),
+            err(".macro w n\nnoop")
+        );
+        assert_eq!(
+            ParseError::new(2, 5, "", "an integer operand"),
+            err(".macro w\naddx\n.endm\nw")
+        );
+        assert_eq!(
+            ParseError::new(2, 1, "w", "a macro that doesn't call itself"),
+            err(".macro w\nw\n.endm\nw")
+        );
+    }
+
+    #[test]
+    fn screen_geometry() {
+        let input = parse_input("addx 3\naddx -2\nnoop\naddx 5\nnoop").unwrap();
+        let run = |screen| {
+            let mut vm = Vm::with_screen(&input, screen);
+            vm.run().map(|_| vm.out.to_string())
+        };
+        let screen = Screen {
+            width: 4,
+            height: 2,
+            sprite: 2,
+            overrun: Overrun::Error,
+        };
+        assert_eq!(
+            Err(CrtError::Overrun(OverrunError {
+                cycles: 7,
+                pixels: 6
+            })),
+            run(Screen { width: 3, ..screen })
+        );
+        assert_eq!(Ok(".#..\n..#.".to_string()), run(screen));
+        assert_eq!(
+            Ok("##.#\n.##.".to_string()),
+            run(Screen {
+                sprite: 3,
+                ..screen
+            })
+        );
+        let screen = Screen {
+            height: 1,
+            ..screen
+        };
+        assert_eq!(
+            Ok(".#..".to_string()),
+            run(Screen {
+                overrun: Overrun::Clip,
+                ..screen
+            })
+        );
+        assert_eq!(
+            Ok("..#.".to_string()),
+            run(Screen {
+                overrun: Overrun::Wrap,
+                ..screen
+            })
+        );
+    }
+
+    #[test]
+    fn endless_loop() {
+        let input = parse_input("jmp 0").unwrap();
+        assert_eq!(
+            Err(CrtError::Overrun(OverrunError {
+                cycles: 241,
+                pixels: 240
+            })),
+            solve_d10_pt2(&input)
+        );
+        let mut vm = Vm::with_screen(
+            &input,
+            Screen {
+                overrun: Overrun::Wrap,
+                ..Screen::default()
+            },
+        );
+        assert_eq!(
+            Err(CrtError::Runaway {
+                cycles: Vm::CYCLE_LIMIT + 1
+            }),
+            vm.run()
+        );
+    }
+
+    fn draw(program: &[Ins]) -> String {
+        let mut vm = Vm::new(program);
+        vm.run().unwrap();
+        vm.out.to_string()
+    }
+
+    #[test]
+    fn synthesize_example() {
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        let image = draw(&input);
+        let program = synthesize(&image).unwrap();
+        assert_eq!(image, draw(&program));
+        assert!(program.len() < input.len());
+
+        let blank = vec![".".repeat(40); 6].join("\n");
+        assert_eq!(Ok(vec![]), synthesize(&blank));
+    }
+
+    #[test]
+    fn synthesize_letters() {
+        let program = synthesize(LETTERS).unwrap();
+        let mut vm = Vm::new(&program);
+        vm.run().unwrap();
+        assert_eq!(Ok("BRJLFULP".to_string()), vm.out.read());
+    }
+
+    #[test]
+    fn unsynthesizable() {
+        // X starts at 1, lighting the first 3 pixels for at least 2 cycles
+        let mut rows = vec![".".repeat(40); 6];
+        rows[1] = "#".repeat(40);
+        assert_eq!(
+            Err(SynthError::Unreachable { row: 0, col: 0 }),
+            synthesize(&rows.join("\n"))
+        );
+        rows[0] = "#.".to_string() + &".".repeat(38);
+        assert_eq!(
+            Err(SynthError::Unreachable { row: 0, col: 1 }),
+            synthesize(&rows.join("\n"))
+        );
+        rows[0] += ".";
+        assert_eq!(
+            Err(SynthError::Malformed(ParseError::new(
+                1,
+                41,
+                ".",
+                "the end of the row"
+            ))),
+            synthesize(&rows.join("\n"))
+        );
+    }
+
+    fn programs() -> impl Strategy<Value = Vec<Ins>> {
+        let ins = prop_oneof![Just(Ins::noop()), (-3..=42).prop_map(Ins::addx)];
+        prop::collection::vec(ins, 0..150)
+    }
+
+    proptest! {
+        #[test]
+        fn synthesize_round_trip(program in programs()) {
+            let clip = Screen {
+                overrun: Overrun::Clip,
+                ..Screen::default()
+            };
+            let mut vm = Vm::with_screen(&program, clip);
+            vm.run().unwrap();
+            let image = vm.out.to_string();
+            let synthesized = synthesize(&image).unwrap();
+            prop_assert_eq!(&image, &draw(&synthesized));
+            prop_assert!(synthesized.len() <= program.len());
+        }
+    }
+
+    fn small_crt() -> Crt {
+        let mut crt = Crt::new(&Screen {
+            width: 3,
+            height: 3,
+            ..Screen::default()
+        });
+        for (row, col) in [(0, 0), (0, 1), (1, 1), (2, 2)] {
+            crt.draw(row, col);
+        }
+        crt
+    }
+
+    #[test]
+    fn half_blocks() {
+        let crt = small_crt();
+        assert_eq!("▀█ \n  ▀", crt.half_blocks(false));
+        assert_eq!(
+            "\x1b[32;40m▀█ \x1b[0m\n\x1b[32;40m  ▀\x1b[0m",
+            crt.half_blocks(true)
+        );
+    }
+
+    #[test]
+    fn export_netpbm() {
+        let crt = small_crt();
+        let mut pbm = vec![];
+        crt.write_pbm(NonZeroUsize::MIN, &mut pbm).unwrap();
+        assert_eq!(b"P4\n3 3\n\xc0\x40\x20"[..], pbm[..]);
+
+        let mut pgm = vec![];
+        crt.write_pgm(NonZeroUsize::new(2).unwrap(), &mut pgm)
+            .unwrap();
+        let (header, pixels) = pgm.split_at(b"P5\n6 6\n255\n".len());
+        assert_eq!(b"P5\n6 6\n255\n", header);
+        assert_eq!([0, 0, 0, 0, 255, 255], pixels[6..12]);
+        assert_eq!([255, 255, 255, 255, 0, 0], pixels[30..]);
+    }
+
+    #[test]
+    fn export_png() {
+        assert_eq!(0xcbf4_3926, crc32(b"123456789".iter()));
+        assert_eq!(0x11e6_0398, adler32(b"Wikipedia"));
+
+        let crt = small_crt();
+        let mut png = vec![];
+        crt.write_png(NonZeroUsize::new(3).unwrap(), &mut png)
+            .unwrap();
+        assert_eq!(b"\x89PNG\r\n\x1a\n", &png[..8]);
+        assert_eq!(
+            b"\0\0\0\x0dIHDR\0\0\0\x09\0\0\0\x09\x08\0\0\0\0",
+            &png[8..29]
+        );
+        assert_eq!(b"\0\0\0\0IEND\xae\x42\x60\x82", &png[png.len() - 12..]);
+
+        // a single stored block holding every row, each starting with its filter
+        let data = &png[41..png.len() - 16];
+        let raw = &data[7..data.len() - 4];
+        assert_eq!([0x78, 0x01, 1, 90, 0, !90, !0], data[..7]);
+        assert_eq!(9 * 10, raw.len());
+        assert_eq!([0, 0, 0, 0, 0, 0, 0, 255, 255, 255], raw[..10]);
+        assert_eq!(adler32(raw).to_be_bytes(), data[data.len() - 4..]);
+    }
 }
@@ -1,114 +1,654 @@
-use std::cell::Cell;
-use std::collections::VecDeque;
-use std::fmt::{Debug, Formatter, Result as FmtResult};
-use std::rc::Rc;
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::error::Error;
+use std::fmt::{Debug, Display, Formatter, Result as FmtResult};
 use std::str::FromStr;
 
+use crate::error::{parse_line, ParseError};
+use crate::solution::Puzzle;
+
 #[aoc_generator(day11)]
-fn parse_input(input: &str) -> Vec<Monkey> {
-    input
+fn parse_input(input: &str) -> Result<Vec<Monkey>, ParseError> {
+    let parsed = input
         .split("\n\n")
-        .map(|m| m.parse::<Monkey>().unwrap())
-        .collect()
+        .map(|m| parse_monkey(input, m))
+        .collect::<Result<Vec<Parsed>, ParseError>>()?;
+    // monkeys are listed in order, and only throw to monkeys that are there
+    let count = parsed.len();
+    for (i, p) in parsed.iter().enumerate() {
+        if p.monkey.id != i {
+            return Err(ParseError::locate(input, p.id, &format!("monkey id {}", i)));
+        }
+        let test = &p.monkey.test;
+        for (target, at) in [test.if_true, test.if_false].into_iter().zip(p.targets) {
+            if target >= count {
+                let expected = format!("a monkey id below {}", count);
+                return Err(ParseError::locate(input, at, &expected));
+            }
+        }
+    }
+    Ok(parsed.into_iter().map(|p| p.monkey).collect())
 }
 
 #[aoc(day11, part1)]
-fn solve_d11_pt1(monkeys: &Vec<Monkey>) -> u32 {
-    dbg!(&monkeys);
-    todo!()
+fn solve_d11_pt1(monkeys: &[Monkey]) -> Result<usize, GameError> {
+    let mut game = Game::new(monkeys, true)?;
+    game.run_for(20)?;
+    Ok(game.top_n_monkey_biz(2))
 }
 
-struct Game {
-    monkeys: Vec<Rc<Cell<Monkey>>>,
+#[aoc(day11, part2)]
+fn solve_d11_pt2(monkeys: &[Monkey]) -> Result<usize, GameError> {
+    let mut game = Game::new(monkeys, false)?;
+    game.run_for(10_000)?;
+    Ok(game.top_n_monkey_biz(2))
+}
+
+/// Why a game can't be played.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GameError {
+    /// Without relief, the least common multiple of the divisors doesn't fit in an item's worry
+    /// level.
+    Modulus,
+    /// Without relief, a monkey's operation uses `-` or `/`, which give the wrong answer once
+    /// worry is reduced by the modulus.
+    NotModular { monkey: usize },
+    /// A monkey's operation on an item went below zero, divided by zero or overflowed.
+    Worry { monkey: usize, worry: Item },
+}
+
+impl Display for GameError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        match self {
+            GameError::Modulus => write!(f, "the divisors have no common multiple small enough"),
+            GameError::NotModular { monkey } => write!(
+                f,
+                "monkey {} can only add and multiply when worry isn't relieved",
+                monkey
+            ),
+            GameError::Worry { monkey, worry } => write!(
+                f,
+                "monkey {} can't work out the worry level of an item at {}",
+                monkey, worry
+            ),
+        }
+    }
+}
+
+impl Error for GameError {}
+
+pub struct Game {
+    monkeys: Vec<Monkey>,
+    /// Whether worry drops to a third after each inspection, as in part 1.
+    relief: bool,
+    /// Without relief, worry is kept below the least common multiple of every monkey's divisor.
+    /// That doesn't change the outcome of any monkey's test. Unused, and 1, with relief.
+    modulus: Item,
+    /// How many rounds have been played.
+    round: usize,
 }
 
 impl Game {
-    fn run_for(&mut self, rounds: usize) {
-        for r in 0..rounds {
-            self.round();
+    /// Without relief, fails unless every monkey's operation only adds and multiplies.
+    pub fn new(monkeys: &[Monkey], relief: bool) -> Result<Self, GameError> {
+        if let Some(m) = monkeys.iter().find(|m| !relief && !m.op.is_modular()) {
+            return Err(GameError::NotModular { monkey: m.id });
         }
+        let modulus = match relief {
+            true => 1,
+            false => monkeys
+                .iter()
+                .try_fold(1, |acc, m| lcm(acc, m.test.divisor))
+                .ok_or(GameError::Modulus)?,
+        };
+        Ok(Self {
+            monkeys: monkeys.to_vec(),
+            relief,
+            modulus,
+            round: 0,
+        })
     }
 
-    fn round(&self) {
-        self.monkeys.iter().map(|m| {
-            let thrown = *m.into_inner().turn().to_vec();
-        });
+    /// Play `rounds` more rounds.
+    ///
+    /// Without relief every item eventually loops through the same states, so rather than playing
+    /// every round this follows each item until it repeats itself and extrapolates from there,
+    /// which is fast for any number of rounds. Items end up with the monkey and worry level
+    /// playing would leave them with, though each monkey holds them in the order they started in.
+    ///
+    /// Stops at the first operation that fails, which leaves the game partway through a round.
+    pub fn run_for(&mut self, rounds: usize) -> Result<(), GameError> {
+        match self.relief {
+            true => (0..rounds).try_for_each(|_| self.round(None)),
+            false => self.extrapolate(rounds),
+        }
     }
 
-    fn top_n_monkey_biz(&self, n: usize) -> usize {
+    fn extrapolate(&mut self, rounds: usize) -> Result<(), GameError> {
+        let mut placed = vec![VecDeque::new(); self.monkeys.len()];
+        let mut activity = vec![0; self.monkeys.len()];
+        for m in 0..self.monkeys.len() {
+            for (item, worry) in std::mem::take(&mut self.monkeys[m].items) {
+                let start = Held { holder: m, worry };
+                let end = self.follow(start, rounds, &mut activity)?;
+                placed[end.holder].push_back((item, end.worry));
+            }
+        }
+        for ((monkey, items), activity) in self.monkeys.iter_mut().zip(placed).zip(activity) {
+            monkey.items = items;
+            monkey.activity += activity;
+        }
+        self.round += rounds;
+        Ok(())
+    }
+
+    /// Where an item starting out as `start` ends up after `rounds` rounds, adding its
+    /// inspections to `activity`.
+    fn follow(
+        &self,
+        start: Held,
+        rounds: usize,
+        activity: &mut [usize],
+    ) -> Result<Held, GameError> {
+        let mut states = vec![start];
+        let mut seen = HashMap::from([(start, 0)]);
+        // the monkeys that inspected the item, with where each round starts in that list
+        let mut inspected = vec![];
+        let mut marks = vec![0];
+        let (first, len) = loop {
+            let round = states.len();
+            if round > rounds {
+                for &m in &inspected {
+                    activity[m] += 1;
+                }
+                return Ok(states[rounds]);
+            }
+            let next = self.item_round(states[round - 1], &mut inspected)?;
+            marks.push(inspected.len());
+            if let Some(&first) = seen.get(&next) {
+                break (first, round - first);
+            }
+            seen.insert(next, round);
+            states.push(next);
+        };
+
+        let (cycles, rest) = ((rounds - first) / len, (rounds - first) % len);
+        for &m in &inspected[..marks[first]] {
+            activity[m] += 1;
+        }
+        for &m in &inspected[marks[first]..] {
+            activity[m] += cycles;
+        }
+        for &m in &inspected[marks[first]..marks[first + rest]] {
+            activity[m] += 1;
+        }
+        Ok(states[first + rest])
+    }
+
+    /// Play a round for a single item, which only depends on the item itself. Pushes every
+    /// monkey that inspects it to `inspected`.
+    fn item_round(&self, mut at: Held, inspected: &mut Vec<usize>) -> Result<Held, GameError> {
+        loop {
+            let monkey = &self.monkeys[at.holder];
+            let (_, worry) = monkey.inspect(at.worry, self.relief, self.modulus)?;
+            inspected.push(at.holder);
+            let to = monkey.test.target(worry);
+            // a monkey whose turn already came up this round holds on until the next
+            let done = to <= at.holder;
+            at = Held { holder: to, worry };
+            if done {
+                return Ok(at);
+            }
+        }
+    }
+
+    /// Play `rounds` rounds, yielding everything that happens along the way, up to the first
+    /// operation that fails.
+    pub fn trace(&mut self, rounds: usize) -> impl Iterator<Item = Result<Event, GameError>> + '_ {
+        let mut left = rounds;
+        let mut pending = VecDeque::new();
+        std::iter::from_fn(move || loop {
+            if let Some(event) = pending.pop_front() {
+                return Some(event);
+            }
+            if left == 0 {
+                return None;
+            }
+            left -= 1;
+            let mut events = vec![];
+            let played = self.round(Some(&mut events));
+            pending.extend(events.into_iter().map(Ok));
+            if let Err(e) = played {
+                pending.push_back(Err(e));
+                left = 0;
+            }
+        })
+    }
+
+    /// Let every monkey take a turn, in order, recording what happens in `events` if given.
+    fn round(&mut self, mut events: Option<&mut Vec<Event>>) -> Result<(), GameError> {
+        self.round += 1;
+        for m in 0..self.monkeys.len() {
+            if let Some(events) = events.as_mut() {
+                events.push(Event::Turn {
+                    round: self.round,
+                    monkey: m,
+                });
+            }
+            // only the items held at the start of the turn, in case the monkey throws to itself
+            for _ in 0..self.monkeys[m].items.len() {
+                let Some(throw) = self.monkeys[m].inspect_next(self.relief, self.modulus)? else {
+                    break;
+                };
+                self.monkeys[throw.to].catch_item(throw.item, throw.after);
+                if let Some(events) = events.as_mut() {
+                    let monkey = &self.monkeys[m];
+                    events.push(Event::Inspect(Inspection {
+                        round: self.round,
+                        monkey: m,
+                        item: throw.item,
+                        before: throw.before,
+                        op: monkey.op.clone(),
+                        after_op: throw.after_op,
+                        relief: self.relief,
+                        modulus: self.modulus,
+                        after: throw.after,
+                        divisor: monkey.test.divisor,
+                        target: throw.to,
+                    }));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// How many rounds have been played so far.
+    pub fn rounds(&self) -> usize {
+        self.round
+    }
+
+    pub fn monkeys(&self) -> &[Monkey] {
+        &self.monkeys
+    }
+
+    /// Product of the activity of the `n` most active monkeys.
+    pub fn top_n_monkey_biz(&self, n: usize) -> usize {
         let mut sorted = self
             .monkeys
             .iter()
-            .map(|m| m.into_inner().activity)
+            .map(|m| m.activity)
             .collect::<Vec<usize>>();
-        sorted.sort();
-        sorted.iter().take(n).fold(0, |acc, a| acc + a)
+        sorted.sort_unstable_by(|a, b| b.cmp(a));
+        sorted.iter().take(n).product()
+    }
+}
+
+/// Something that happened during a round, see [`Game::trace`].
+///
+/// Displays the way the puzzle describes the example, one event after another.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Event {
+    /// A monkey starts its turn.
+    Turn { round: usize, monkey: usize },
+    /// A monkey inspects and throws an item.
+    Inspect(Inspection),
+}
+
+/// A monkey inspecting an item and throwing it on.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Inspection {
+    pub round: usize,
+    /// The monkey doing the inspecting.
+    pub monkey: usize,
+    pub item: ItemId,
+    /// Worry level when the inspection starts.
+    pub before: Item,
+    /// The monkey's operation.
+    pub op: Expr,
+    /// Worry level straight after the operation.
+    pub after_op: Wide,
+    /// Whether worry was divided by 3, otherwise it was reduced by `modulus`.
+    pub relief: bool,
+    pub modulus: Item,
+    /// Worry level once the item is thrown.
+    pub after: Item,
+    /// The divisor the monkey tests worry levels against.
+    pub divisor: Item,
+    /// The monkey the item is thrown to.
+    pub target: usize,
+}
+
+impl Inspection {
+    pub fn divisible(&self) -> bool {
+        self.after.is_multiple_of(self.divisor)
+    }
+}
+
+impl Display for Event {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let i = match self {
+            Event::Turn { monkey, .. } => return write!(f, "Monkey {}:", monkey),
+            Event::Inspect(i) => i,
+        };
+        writeln!(
+            f,
+            "  Monkey inspects an item with a worry level of {}.",
+            i.before
+        )?;
+        let old = || Box::new(Expr::Old);
+        match &i.op {
+            Expr::Mul(a, b) if *a == old() && *b == old() => writeln!(
+                f,
+                "    Worry level is multiplied by itself to {}.",
+                i.after_op
+            )?,
+            Expr::Mul(a, b) if *a == old() => writeln!(
+                f,
+                "    Worry level is multiplied by {} to {}.",
+                b, i.after_op
+            )?,
+            Expr::Add(a, b) if *a == old() => {
+                writeln!(f, "    Worry level increases by {} to {}.", b, i.after_op)?
+            }
+            op => writeln!(f, "    Worry level becomes {} = {}.", op, i.after_op)?,
+        }
+        match i.relief {
+            true => writeln!(
+                f,
+                "    Monkey gets bored with item. Worry level is divided by 3 to {}.",
+                i.after
+            )?,
+            false => writeln!(
+                f,
+                "    Worry level is reduced modulo {} to {}.",
+                i.modulus, i.after
+            )?,
+        }
+        match i.divisible() {
+            true => writeln!(f, "    Current worry level is divisible by {}.", i.divisor)?,
+            false => writeln!(
+                f,
+                "    Current worry level is not divisible by {}.",
+                i.divisor
+            )?,
+        }
+        write!(
+            f,
+            "    Item with worry level {} is thrown to monkey {}.",
+            i.after, i.target
+        )
     }
 }
 
-type Item = u32;
+/// Every monkey that held each item inspected in `events`, in order, starting with the one it
+/// started with.
+pub fn provenance<'a>(events: impl IntoIterator<Item = &'a Event>) -> BTreeMap<ItemId, Vec<usize>> {
+    let mut history = BTreeMap::new();
+    for event in events {
+        if let Event::Inspect(i) = event {
+            history
+                .entry(i.item)
+                .or_insert_with(|| vec![i.monkey])
+                .push(i.target);
+        }
+    }
+    history
+}
+
+/// Names an item by where it is listed in the input, as it keeps the same id wherever it's thrown.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ItemId {
+    /// The monkey that started out holding the item.
+    pub monkey: usize,
+    /// Its place in that monkey's starting items.
+    pub index: usize,
+}
+
+/// An item's whereabouts at the start of a round.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+struct Held {
+    holder: usize,
+    worry: Item,
+}
+
+/// An item being thrown at the end of its inspection.
+struct Throw {
+    item: ItemId,
+    before: Item,
+    after_op: Wide,
+    after: Item,
+    to: usize,
+}
+
+type Item = u64;
+/// Wide enough to hold the result of a single `+` or `*` on any two `Item`s, which is all the
+/// puzzle input uses.
+pub type Wide = u128;
+
+fn gcd(a: Item, b: Item) -> Item {
+    match b {
+        0 => a,
+        _ => gcd(b, a % b),
+    }
+}
+
+/// Least common multiple of `a` and `b`, or `None` if it doesn't fit in an `Item`.
+fn lcm(a: Item, b: Item) -> Option<Item> {
+    (a / gcd(a, b)).checked_mul(b)
+}
+
+/// The right hand side of a monkey's `new = ...` operation.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Expr {
+    /// The worry level before the operation.
+    Old,
+    Const(Item),
+    Add(Box<Expr>, Box<Expr>),
+    Sub(Box<Expr>, Box<Expr>),
+    Mul(Box<Expr>, Box<Expr>),
+    Div(Box<Expr>, Box<Expr>),
+}
+
+/// The integer types an [`Expr`] can be evaluated with, each operation returning `None` where
+/// the primitive `checked_*` method would.
+pub trait Checked: Copy + From<Item> {
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+    fn checked_div(self, rhs: Self) -> Option<Self>;
+}
+
+macro_rules! impl_checked {
+    ($($t:ty),*) => {$(
+        impl Checked for $t {
+            fn checked_add(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_add(self, rhs)
+            }
+
+            fn checked_sub(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_sub(self, rhs)
+            }
+
+            fn checked_mul(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_mul(self, rhs)
+            }
+
+            fn checked_div(self, rhs: Self) -> Option<Self> {
+                <$t>::checked_div(self, rhs)
+            }
+        }
+    )*};
+}
+
+impl_checked!(u64, u128);
+
+impl Expr {
+    /// Evaluate with `old` as the current worry level, or `None` if that goes below zero,
+    /// divides by zero or overflows.
+    pub fn eval<T: Checked>(&self, old: T) -> Option<T> {
+        match self {
+            Expr::Old => Some(old),
+            Expr::Const(c) => Some(T::from(*c)),
+            Expr::Add(a, b) => a.eval(old)?.checked_add(b.eval(old)?),
+            Expr::Sub(a, b) => a.eval(old)?.checked_sub(b.eval(old)?),
+            Expr::Mul(a, b) => a.eval(old)?.checked_mul(b.eval(old)?),
+            Expr::Div(a, b) => a.eval(old)?.checked_div(b.eval(old)?),
+        }
+    }
+
+    /// Whether it gives the same result modulo anything whether or not its operands were reduced
+    /// first, which only holds when it just adds and multiplies.
+    pub fn is_modular(&self) -> bool {
+        match self {
+            Expr::Old | Expr::Const(_) => true,
+            Expr::Add(a, b) | Expr::Mul(a, b) => a.is_modular() && b.is_modular(),
+            Expr::Sub(..) | Expr::Div(..) => false,
+        }
+    }
+
+    /// How tightly the expression binds, to know when it needs parentheses.
+    fn precedence(&self) -> u8 {
+        match self {
+            Expr::Old | Expr::Const(_) => 3,
+            Expr::Mul(..) | Expr::Div(..) => 2,
+            Expr::Add(..) | Expr::Sub(..) => 1,
+        }
+    }
+}
+
+/// Prints the expression back the way the input writes it, with only the parentheses needed to
+/// parse it into the same tree.
+impl Display for Expr {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        let (a, op, b) = match self {
+            Expr::Old => return write!(f, "old"),
+            Expr::Const(c) => return write!(f, "{}", c),
+            Expr::Add(a, b) => (a, '+', b),
+            Expr::Sub(a, b) => (a, '-', b),
+            Expr::Mul(a, b) => (a, '*', b),
+            Expr::Div(a, b) => (a, '/', b),
+        };
+        // operators are left associative, so only the right hand side needs parentheses at the
+        // same precedence
+        match a.precedence() < self.precedence() {
+            true => write!(f, "({})", a)?,
+            false => write!(f, "{}", a)?,
+        }
+        write!(f, " {} ", op)?;
+        match b.precedence() <= self.precedence() {
+            true => write!(f, "({})", b),
+            false => write!(f, "{}", b),
+        }
+    }
+}
+
+impl FromStr for Expr {
+    type Err = ParseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_line(s, s, parser::expr, "an arithmetic expression")
+    }
+}
+
+/// Decides who an item is thrown to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Test {
+    /// The item goes to `if_true` when its worry level is divisible by this.
+    pub divisor: Item,
+    pub if_true: usize,
+    pub if_false: usize,
+}
+
+impl Test {
+    /// The id of the monkey an item with this worry level is thrown to.
+    pub fn target(&self, worry: Item) -> usize {
+        match worry.is_multiple_of(self.divisor) {
+            true => self.if_true,
+            false => self.if_false,
+        }
+    }
+}
 
 /// Monkeh
-struct Monkey {
+#[derive(Clone)]
+pub struct Monkey {
     /// The id of the monkey as defined by the input.
     id: usize,
-    /// A list of items the monkey starts with.
-    items: VecDeque<Item>,
+    /// The items the monkey holds along with their worry levels, starting with those it starts with.
+    items: VecDeque<(ItemId, Item)>,
     /// The operation to apply to the currently held item.
-    op: Box<dyn Fn(Item) -> u32>,
-    /// The function used to decide which monkey to throw to.
-    test: Box<dyn Fn(Item) -> bool>,
-    /// The IDs of the monkeys the item will be thrown to if test evaluates true or false, respectively.
-    throw: (usize, usize),
+    op: Expr,
+    /// Where the item goes once inspected.
+    test: Test,
     /// Activity count, how many times the monkey has inspected an item over all rounds
     activity: usize,
 }
 
 impl Monkey {
-    fn new(
-        id: usize,
-        items: VecDeque<u32>,
-        op: Box<dyn Fn(u32) -> u32>,
-        test: Box<dyn Fn(u32) -> bool>,
-        throw: (usize, usize),
-    ) -> Self {
+    fn new(id: usize, items: VecDeque<Item>, op: Expr, test: Test) -> Self {
         Self {
             id,
-            items,
+            items: items
+                .into_iter()
+                .enumerate()
+                .map(|(index, worry)| (ItemId { monkey: id, index }, worry))
+                .collect(),
             op,
             test,
-            throw,
             activity: 0,
         }
     }
 
-    fn turn(&mut self) -> Vec<(usize, Item)> {
-        let mut thrown_items: Vec<(usize, Item)> = vec![];
+    pub fn activity(&self) -> usize {
+        self.activity
+    }
+
+    /// The worry levels of the items the monkey holds, in the order it will inspect them.
+    pub fn items(&self) -> impl Iterator<Item = Item> + '_ {
+        self.items.iter().map(|&(_, worry)| worry)
+    }
 
-        while let Some(item) = self.inspect_next() {
-            thrown_items.push(item);
-        }
+    pub fn op(&self) -> &Expr {
+        &self.op
+    }
 
-        thrown_items
+    pub fn test(&self) -> &Test {
+        &self.test
     }
 
-    fn inspect_next(&mut self) -> Option<(usize, Item)> {
-        let mut items = &mut self.items;
-        let item = items.pop_front();
-        if item.is_none() {
-            return None;
-        }
-        let item = (self.op)(item.unwrap());
+    /// The worry level of an item after the operation, and once calmed down again.
+    fn inspect(&self, worry: Item, relief: bool, modulus: Item) -> Result<(Wide, Item), GameError> {
+        let failed = GameError::Worry {
+            monkey: self.id,
+            worry,
+        };
+        let after_op = self.op.eval(Wide::from(worry)).ok_or(failed)?;
+        let after = match relief {
+            true => after_op / 3,
+            false => after_op % Wide::from(modulus),
+        };
+        let after = Item::try_from(after).map_err(|_| failed)?;
+        Ok((after_op, after))
+    }
+
+    /// Inspect the next item, if any, putting it back if that fails.
+    fn inspect_next(&mut self, relief: bool, modulus: Item) -> Result<Option<Throw>, GameError> {
+        let Some(&(item, before)) = self.items.front() else {
+            return Ok(None);
+        };
+        let (after_op, after) = self.inspect(before, relief, modulus)?;
+        self.items.pop_front();
         self.activity += 1;
-        match (self.test)(item) {
-            true => Some((self.throw.0, item)),
-            false => Some((self.throw.1, item)),
-        }
+        Ok(Some(Throw {
+            item,
+            before,
+            after_op,
+            after,
+            to: self.test.target(after),
+        }))
     }
 
-    fn catch_item(&self, incoming: Item) {
-        let mut items = &mut self.items;
-        items.push_back(incoming);
+    fn catch_item(&mut self, item: ItemId, worry: Item) {
+        self.items.push_back((item, worry));
     }
 }
 
@@ -116,124 +656,225 @@ impl Debug for Monkey {
     fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
         f.debug_struct("Monkey")
             .field("id", &self.id)
-            .field("items", &self.items)
-            .field("op", &"Box<dyn Fn(Item) -> u32>")
-            .field("test", &"Box<dyn Fn(Item) -> bool>")
-            .field("throw", &self.throw)
+            .field("items", &self.items().collect::<Vec<Item>>())
+            .field("op", &format_args!("new = {}", self.op))
+            .field("test", &self.test)
+            .field("activity", &self.activity)
             .finish()
     }
 }
 
-#[derive(Debug)]
-enum Op {
-    Mul,
-    Add,
-}
+pub struct Day11;
+
+impl Puzzle for Day11 {
+    const DAY: u32 = 11;
+
+    type Input = Vec<Monkey>;
+    type Output1 = usize;
+    type Output2 = usize;
+    type Error = GameError;
+
+    fn parse(&self, input: &str) -> Result<Self::Input, ParseError> {
+        parse_input(input)
+    }
+
+    fn part1(&self, monkeys: &Self::Input) -> Result<usize, GameError> {
+        solve_d11_pt1(monkeys)
+    }
 
-#[derive(Debug)]
-struct MonkeyParseError;
+    fn part2(&self, monkeys: &Self::Input) -> Result<usize, GameError> {
+        solve_d11_pt2(monkeys)
+    }
+}
 
 impl FromStr for Monkey {
-    type Err = MonkeyParseError;
+    type Err = ParseError;
 
     fn from_str(s: &str) -> Result<Self, Self::Err> {
-        let mut lines = s.lines();
-        let id = parse_id(lines.next().unwrap());
-        let start = parse_start(lines.next().unwrap());
-        let op = parse_op(lines.next().unwrap());
-        let test = parse_test(lines.next().unwrap());
-        let throw = parse_throw(lines.next().unwrap(), lines.next().unwrap());
-
-        Ok(Monkey::new(id, start, op, test, throw))
-    }
-}
-
-fn parse_id(s: &str) -> usize {
-    s.split_whitespace().collect::<Vec<&str>>()[1]
-        .chars()
-        .next()
-        .unwrap()
-        .to_digit(10)
-        .unwrap()
-        .try_into()
-        .unwrap()
-}
-
-fn parse_start(s: &str) -> VecDeque<Item> {
-    s.split(":").collect::<Vec<&str>>()[1]
-        .trim()
-        .split(", ")
-        .map(|n| n.parse::<Item>().unwrap())
-        .collect()
-}
-
-fn parse_op(s: &str) -> Box<dyn Fn(u32) -> u32> {
-    let mut op_str = s
-        .split(":")
-        .nth(1)
-        .unwrap()
-        .trim()
-        .split(" = ")
-        .nth(1)
-        .unwrap()
-        .split_whitespace();
-    let op = match op_str.nth(1).unwrap() {
-        "*" => Op::Mul,
-        "+" => Op::Add,
-        _ => unreachable!(),
+        parse_monkey(s, s).map(|p| p.monkey)
+    }
+}
+
+/// A monkey along with where its id and the ids it throws to are in the input, to check them
+/// against the other monkeys.
+struct Parsed<'a> {
+    monkey: Monkey,
+    id: &'a str,
+    /// The `if_true` and `if_false` ids.
+    targets: [&'a str; 2],
+}
+
+/// Parse the monkey described by `s`, a block of lines taken from `input`.
+fn parse_monkey<'a>(input: &str, s: &'a str) -> Result<Parsed<'a>, ParseError> {
+    let mut lines = s.lines();
+    let mut next = |expected| {
+        lines
+            .next()
+            .ok_or_else(|| ParseError::locate(input, &s[s.len()..], expected))
+    };
+    let id_text = parse_id(input, next("`Monkey`")?)?;
+    let id = number(input, id_text, "a monkey id")?;
+    let start = parse_start(input, next("`Starting items:`")?)?;
+    let op = parse_op(input, next("`Operation: new =`")?)?;
+    let divisor = parse_test(input, next("`Test: divisible by`")?)?;
+    let targets = parse_throw(
+        input,
+        next("`If true: throw to monkey`")?,
+        next("`If false: throw to monkey`")?,
+    )?;
+    let test = Test {
+        divisor,
+        if_true: number(input, targets[0], "a monkey id")?,
+        if_false: number(input, targets[1], "a monkey id")?,
+    };
+
+    Ok(Parsed {
+        monkey: Monkey::new(id, start, op, test),
+        id: id_text,
+        targets,
+    })
+}
+
+/// Strip the leading `label` off of `line`, returning what follows it.
+fn field<'a>(input: &str, line: &'a str, label: &str) -> Result<&'a str, ParseError> {
+    let line = line.trim_start();
+    line.strip_prefix(label)
+        .map(str::trim)
+        .ok_or_else(|| ParseError::locate(input, line, &format!("`{}`", label)))
+}
+
+fn number<T: FromStr>(input: &str, s: &str, expected: &str) -> Result<T, ParseError> {
+    s.parse::<T>()
+        .map_err(|_| ParseError::locate(input, s, expected))
+}
+
+/// The monkey's id, as written.
+fn parse_id<'a>(input: &str, s: &'a str) -> Result<&'a str, ParseError> {
+    let id = field(input, s, "Monkey")?;
+    id.strip_suffix(':')
+        .ok_or_else(|| ParseError::locate(input, &id[id.len()..], "`:`"))
+}
+
+/// The worry levels of the items a monkey starts with, which may be none.
+fn parse_start(input: &str, s: &str) -> Result<VecDeque<Item>, ParseError> {
+    match field(input, s, "Starting items:")? {
+        "" => Ok(VecDeque::new()),
+        items => items
+            .split(", ")
+            .map(|n| number(input, n, "a worry level"))
+            .collect(),
+    }
+}
+
+fn parse_op(input: &str, s: &str) -> Result<Expr, ParseError> {
+    let op = field(input, s, "Operation: new =")?;
+    parse_line(input, op, parser::expr, "an arithmetic expression")
+}
+
+fn parse_test(input: &str, s: &str) -> Result<Item, ParseError> {
+    let divisor = field(input, s, "Test: divisible by")?;
+    match number::<Item>(input, divisor, "a divisor")? {
+        0 => Err(ParseError::locate(input, divisor, "a non-zero divisor")),
+        d => Ok(d),
+    }
+}
+
+/// The `if_true` and `if_false` monkey ids, as written.
+fn parse_throw<'a>(input: &str, s1: &'a str, s2: &'a str) -> Result<[&'a str; 2], ParseError> {
+    Ok([
+        field(input, s1, "If true: throw to monkey")?,
+        field(input, s2, "If false: throw to monkey")?,
+    ])
+}
+
+mod parser {
+    use super::{Expr, Item};
+    use nom::{
+        branch::alt,
+        bytes::complete::tag,
+        character::complete::{char, digit1, one_of, space0},
+        combinator::{cut, map, map_res, value},
+        multi::fold_many0,
+        sequence::{delimited, pair},
+        IResult,
     };
-    // dbg!(&op_str);
-    let b = op_str.next().unwrap().parse::<u32>();
-
-    match op {
-        Op::Mul => Box::new(move |a| a * b.as_ref().unwrap_or(&a)),
-        Op::Add => Box::new(move |a| a + b.as_ref().unwrap_or(&a)),
-    }
-}
-
-fn parse_test(s: &str) -> Box<dyn Fn(u32) -> bool> {
-    let b = s
-        .split(":")
-        .nth(1)
-        .unwrap()
-        .trim()
-        .split_whitespace()
-        .nth(2)
-        .unwrap()
-        .parse::<u32>()
-        .unwrap();
-    Box::new(move |a| a % b == 0)
-}
-
-// returns (if_true_id, if_false_id)
-fn parse_throw(s1: &str, s2: &str) -> (usize, usize) {
-    let t = s1
-        .trim()
-        .split_whitespace()
-        .nth(5)
-        .unwrap()
-        .parse::<usize>()
-        .unwrap();
-    let f = s2
-        .trim()
-        .split_whitespace()
-        .nth(5)
-        .unwrap()
-        .parse::<usize>()
-        .unwrap();
-    (t, f)
+
+    /// Sums and differences of terms.
+    pub fn expr(input: &str) -> IResult<&str, Expr> {
+        let (input, first) = term(input)?;
+        fold_many0(
+            pair(token(one_of("+-")), cut(term)),
+            move || first.clone(),
+            |a, (op, b)| match op {
+                '+' => Expr::Add(Box::new(a), Box::new(b)),
+                _ => Expr::Sub(Box::new(a), Box::new(b)),
+            },
+        )(input)
+    }
+
+    /// Products and quotients of factors.
+    fn term(input: &str) -> IResult<&str, Expr> {
+        let (input, first) = factor(input)?;
+        fold_many0(
+            pair(token(one_of("*/")), cut(factor)),
+            move || first.clone(),
+            |a, (op, b)| match op {
+                '*' => Expr::Mul(Box::new(a), Box::new(b)),
+                _ => Expr::Div(Box::new(a), Box::new(b)),
+            },
+        )(input)
+    }
+
+    fn factor(input: &str) -> IResult<&str, Expr> {
+        alt((
+            value(Expr::Old, token(tag("old"))),
+            map(token(map_res(digit1, str::parse::<Item>)), Expr::Const),
+            delimited(token(char('(')), expr, token(char(')'))),
+        ))(input)
+    }
+
+    /// `parser` along with the spaces around it.
+    fn token<'a, O>(
+        parser: impl FnMut(&'a str) -> IResult<&'a str, O>,
+    ) -> impl FnMut(&'a str) -> IResult<&'a str, O> {
+        delimited(space0, parser, space0)
+    }
 }
 
+#[cfg(test)]
 mod tests {
     use super::*;
+    use proptest::prelude::*;
 
     static EXAMPLE_INPUT: &str = include_str!("../input/2022/d11-example.txt");
     static FILE_INPUT: &str = include_str!("../input/2022/day11.txt");
 
+    /// The input describing a single monkey.
+    fn monkey_src(
+        id: usize,
+        items: &[Item],
+        op: &str,
+        divisor: Item,
+        if_true: usize,
+        if_false: usize,
+    ) -> String {
+        let items = items
+            .iter()
+            .map(|i| i.to_string())
+            .collect::<Vec<String>>()
+            .join(", ");
+        format!(
+            "Monkey {}:\n  Starting items: {}\n  Operation: new = {}\n  \
+             Test: divisible by {}\n    If true: throw to monkey {}\n    \
+             If false: throw to monkey {}",
+            id, items, op, divisor, if_true, if_false
+        )
+    }
+
     #[test]
     fn example_pt1() {
-        let input = parse_input(EXAMPLE_INPUT);
-        let expect = 42;
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        let expect = Ok(10605);
         let actual = solve_d11_pt1(&input);
         assert_eq!(expect, actual);
     }
@@ -241,9 +882,313 @@ mod tests {
     #[ignore]
     #[test]
     fn solve_pt1() {
-        let input = parse_input(FILE_INPUT);
-        let expect = 42;
+        let input = parse_input(FILE_INPUT).unwrap();
+        let expect = Ok(42);
         let actual = solve_d11_pt1(&input);
         assert_eq!(expect, actual);
     }
+
+    #[test]
+    fn example_pt2() {
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        let expect = Ok(2713310158);
+        let actual = solve_d11_pt2(&input);
+        assert_eq!(expect, actual);
+    }
+
+    #[ignore]
+    #[test]
+    fn solve_pt2() {
+        let input = parse_input(FILE_INPUT).unwrap();
+        let expect = Ok(42);
+        let actual = solve_d11_pt2(&input);
+        assert_eq!(expect, actual);
+    }
+
+    #[test]
+    fn example_activity() {
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        let mut game = Game::new(&input, true).unwrap();
+        game.run_for(20).unwrap();
+        let activity = game
+            .monkeys()
+            .iter()
+            .map(|m| m.activity())
+            .collect::<Vec<usize>>();
+        assert_eq!(vec![101, 95, 7, 105], activity);
+    }
+
+    #[test]
+    fn modulus_is_lcm() {
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        assert_eq!(23 * 19 * 13 * 17, Game::new(&input, false).unwrap().modulus);
+        assert_eq!(Some(12), lcm(4, 6));
+        assert_eq!(None, lcm(Item::MAX, 2));
+    }
+
+    #[test]
+    fn bad_operations() {
+        let monkey = |op| monkey_src(0, &[1], op, 2, 0, 0);
+        let input = parse_input(&monkey("old - 5")).unwrap();
+        let failed = GameError::Worry {
+            monkey: 0,
+            worry: 1,
+        };
+        assert_eq!(Err(failed), solve_d11_pt1(&input));
+        assert_eq!(
+            Err(GameError::NotModular { monkey: 0 }),
+            solve_d11_pt2(&input)
+        );
+        let input = parse_input(&monkey("old / 0")).unwrap();
+        assert_eq!(Err(failed), solve_d11_pt1(&input));
+
+        let mut game = Game::new(&input, true).unwrap();
+        let events = game.trace(3).collect::<Vec<Result<Event, GameError>>>();
+        assert_eq!(
+            vec![
+                Ok(Event::Turn {
+                    round: 1,
+                    monkey: 0
+                }),
+                Err(failed)
+            ],
+            events
+        );
+        // the item stays with the monkey, uninspected
+        assert_eq!(vec![1], game.monkeys()[0].items().collect::<Vec<Item>>());
+        assert_eq!(0, game.monkeys()[0].activity());
+    }
+
+    #[test]
+    fn modulus_out_of_range() {
+        let big: Item = (1 << 32) + 15;
+        let input = format!(
+            "{}\n\n{}",
+            monkey_src(0, &[1], "old + 1", big, 0, 1),
+            monkey_src(1, &[1], "old + 1", big - 2, 0, 1)
+        );
+        let input = parse_input(&input).unwrap();
+        assert_eq!(Err(GameError::Modulus), solve_d11_pt2(&input));
+        assert!(solve_d11_pt1(&input).is_ok());
+    }
+
+    #[test]
+    fn squares_beyond_item() {
+        // 2^32 + 15 is prime, so squaring any worry level below it overflows an `Item`.
+        let big: Item = (1 << 32) + 15;
+        let monkey = monkey_src(0, &[big - 1], "old * old", big, 0, 0);
+        let mut game = Game::new(&[monkey.parse().unwrap()], false).unwrap();
+        game.run_for(1001).unwrap();
+        // (-1)^2 = 1, then 1 stays 1.
+        assert_eq!(vec![1], game.monkeys()[0].items().collect::<Vec<Item>>());
+    }
+
+    #[test]
+    fn bad_monkey() {
+        let err = parse_input("Monkey 0:\n  Starting items: 79, 98\n  Operation: new = old ^ 19")
+            .unwrap_err();
+        assert_eq!(
+            ParseError::new(3, 24, "^ 19", "an arithmetic expression"),
+            err
+        );
+        let err = parse_input("Monkey 0:\n  Starting items: 79").unwrap_err();
+        assert_eq!(ParseError::new(2, 21, "", "`Operation: new =`"), err);
+    }
+
+    #[test]
+    fn empty_handed() {
+        let input = format!(
+            "{}\n\n{}",
+            monkey_src(0, &[], "old + 1", 2, 1, 1),
+            monkey_src(1, &[3], "old", 2, 0, 0)
+        );
+        let input = parse_input(&input).unwrap();
+        assert_eq!(0, input[0].items().count());
+        let mut game = Game::new(&input, true).unwrap();
+        game.run_for(1).unwrap();
+        let activity = game.monkeys().iter().map(|m| m.activity());
+        assert_eq!(vec![0, 1], activity.collect::<Vec<usize>>());
+        let monkey = "Monkey 0:\n  Starting items:\n  Operation: new = old\n  \
+            Test: divisible by 2\n    If true: throw to monkey 0\n    If false: throw to monkey 0";
+        assert_eq!(0, monkey.parse::<Monkey>().unwrap().items().count());
+    }
+
+    #[test]
+    fn bad_monkey_ids() {
+        let monkey = |id, if_true| monkey_src(id, &[1], "old", 2, if_true, 0);
+        let err = parse_input(&monkey(0, 5)).unwrap_err();
+        assert_eq!(ParseError::new(5, 30, "5", "a monkey id below 1"), err);
+        let err = parse_input(&format!("{}\n\n{}", monkey(0, 0), monkey(2, 0))).unwrap_err();
+        assert_eq!(ParseError::new(8, 8, "2", "monkey id 1"), err);
+        assert!(parse_input(&format!("{}\n\n{}", monkey(0, 1), monkey(1, 0))).is_ok());
+    }
+
+    #[test]
+    fn parse_expr() {
+        let old = || Box::new(Expr::Old);
+        let c = |n| Box::new(Expr::Const(n));
+        assert_eq!(Ok(Expr::Mul(old(), old())), "old * old".parse());
+        assert_eq!(
+            Ok(Expr::Sub(
+                Box::new(Expr::Add(old(), Box::new(Expr::Mul(c(2), old())))),
+                c(1)
+            )),
+            "old + 2 * old - 1".parse()
+        );
+        assert_eq!(
+            Ok(Expr::Div(Box::new(Expr::Add(old(), c(3))), c(2))),
+            " ( old+3 ) / 2".parse()
+        );
+        assert_eq!(
+            Err(ParseError::new(1, 7, ")", "an arithmetic expression")),
+            "(old +)".parse::<Expr>()
+        );
+    }
+
+    #[test]
+    fn eval_and_display() {
+        let expr = "(old + 3) * (old - 1) / 2".parse::<Expr>().unwrap();
+        assert_eq!(Some(30), expr.eval(7u64));
+        assert_eq!(Some(30), expr.eval(7u128));
+        assert_eq!(None, expr.eval(0u64));
+        assert_eq!(None, "old * old".parse::<Expr>().unwrap().eval(u64::MAX));
+        assert_eq!(
+            Some(u128::from(u64::MAX) * u128::from(u64::MAX)),
+            "old * old"
+                .parse::<Expr>()
+                .unwrap()
+                .eval(u128::from(u64::MAX))
+        );
+        assert!(!expr.is_modular());
+        assert!("(old + 3) * old".parse::<Expr>().unwrap().is_modular());
+        assert_eq!("(old + 3) * (old - 1) / 2", expr.to_string());
+        assert_eq!(
+            "old - (old - 1)",
+            "old - (old - 1)".parse::<Expr>().unwrap().to_string()
+        );
+        assert_eq!("old * 19", "old*(19)".parse::<Expr>().unwrap().to_string());
+
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        assert!(format!("{:?}", input[2]).contains("op: new = old * old,"));
+    }
+
+    fn exprs() -> impl Strategy<Value = Expr> {
+        let leaf = prop_oneof![Just(Expr::Old), (0..1000 as Item).prop_map(Expr::Const)];
+        leaf.prop_recursive(4, 32, 2, |inner| {
+            (0..4, inner.clone(), inner).prop_map(|(op, a, b)| {
+                let (a, b) = (Box::new(a), Box::new(b));
+                match op {
+                    0 => Expr::Add(a, b),
+                    1 => Expr::Sub(a, b),
+                    2 => Expr::Mul(a, b),
+                    _ => Expr::Div(a, b),
+                }
+            })
+        })
+    }
+
+    proptest! {
+        #[test]
+        fn display_round_trip(expr in exprs()) {
+            prop_assert_eq!(Ok(expr.clone()), expr.to_string().parse::<Expr>());
+        }
+    }
+
+    #[test]
+    fn example_trace() {
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        let mut game = Game::new(&input, true).unwrap();
+        let text = game
+            .trace(1)
+            .take(3)
+            .map(|e| e.unwrap().to_string())
+            .collect::<Vec<String>>()
+            .join("\n");
+        let expect = "\
+Monkey 0:
+  Monkey inspects an item with a worry level of 79.
+    Worry level is multiplied by 19 to 1501.
+    Monkey gets bored with item. Worry level is divided by 3 to 500.
+    Current worry level is not divisible by 23.
+    Item with worry level 500 is thrown to monkey 3.
+  Monkey inspects an item with a worry level of 98.
+    Worry level is multiplied by 19 to 1862.
+    Monkey gets bored with item. Worry level is divided by 3 to 620.
+    Current worry level is not divisible by 23.
+    Item with worry level 620 is thrown to monkey 3.";
+        assert_eq!(expect, text);
+        assert_eq!(1, game.rounds());
+        assert_eq!(
+            vec![20, 23, 27, 26, 2080, 25, 167, 207, 401, 1046],
+            game.monkeys()
+                .iter()
+                .flat_map(|m| m.items())
+                .collect::<Vec<Item>>()
+        );
+    }
+
+    #[test]
+    fn item_provenance() {
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        let events = Game::new(&input, true)
+            .unwrap()
+            .trace(2)
+            .collect::<Result<Vec<Event>, GameError>>()
+            .unwrap();
+        let history = provenance(&events);
+        // 79 goes to monkey 3 and on to 1 in the first round, then makes it round to 1 again
+        assert_eq!(
+            Some(&vec![0, 3, 1, 2, 3, 1]),
+            history.get(&ItemId {
+                monkey: 0,
+                index: 0
+            })
+        );
+        assert_eq!(10, history.len());
+        let divisible = events
+            .iter()
+            .filter(|e| matches!(e, Event::Inspect(i) if i.round == 1 && i.divisible()))
+            .count();
+        assert_eq!(1, divisible);
+    }
+
+    /// Where each item is, and each monkey's activity.
+    fn snapshot(game: &Game) -> (Vec<(ItemId, usize, Item)>, Vec<usize>) {
+        let mut items = (game.monkeys.iter().enumerate())
+            .flat_map(|(m, monkey)| monkey.items.iter().map(move |&(id, w)| (id, m, w)))
+            .collect::<Vec<(ItemId, usize, Item)>>();
+        items.sort_unstable();
+        let activity = game.monkeys.iter().map(|m| m.activity).collect();
+        (items, activity)
+    }
+
+    #[test]
+    fn extrapolate_matches_playing() {
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        for rounds in [0, 1, 20, 1000, 10_000] {
+            let mut played = Game::new(&input, false).unwrap();
+            (0..rounds).for_each(|_| played.round(None).unwrap());
+            let mut extrapolated = Game::new(&input, false).unwrap();
+            extrapolated.run_for(rounds / 2).unwrap();
+            extrapolated.run_for(rounds - rounds / 2).unwrap();
+            assert_eq!(
+                snapshot(&played),
+                snapshot(&extrapolated),
+                "{} rounds",
+                rounds
+            );
+            assert_eq!(rounds, extrapolated.rounds());
+        }
+    }
+
+    #[test]
+    fn trillion_rounds() {
+        let input = parse_input(EXAMPLE_INPUT).unwrap();
+        let mut game = Game::new(&input, false).unwrap();
+        game.run_for(1_000_000_000_000).unwrap();
+        let (items, activity) = snapshot(&game);
+        assert_eq!(10, items.len());
+        // every item is inspected at least once a round
+        assert!(activity.iter().sum::<usize>() >= 10 * 1_000_000_000_000);
+    }
 }
@@ -0,0 +1,159 @@
+use std::error::Error;
+use std::fmt::{Display, Formatter, Result as FmtResult};
+
+use nom::{combinator::all_consuming, Parser};
+
+/// A malformed puzzle input, pinpointed to where parsing gave up.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ParseError {
+    /// 1-based line number of the offending text.
+    pub line: usize,
+    /// 1-based column (in chars) of the offending text.
+    pub column: usize,
+    /// The text that could not be parsed, cut off at the end of its line.
+    pub found: String,
+    /// A short description of what the parser wanted to see instead.
+    pub expected: String,
+}
+
+impl ParseError {
+    pub fn new(line: usize, column: usize, found: &str, expected: &str) -> Self {
+        Self {
+            line,
+            column,
+            found: found.lines().next().unwrap_or("").to_string(),
+            expected: expected.to_string(),
+        }
+    }
+
+    /// Build an error for `at`, working out its line and column from where it sits inside `src`.
+    ///
+    /// `at` must be a subslice of `src` (e.g. a token split off of it, or the remaining input of a
+    /// nom parser), otherwise the error is reported at the end of `src`.
+    pub fn locate(src: &str, at: &str, expected: &str) -> Self {
+        let start = src.as_ptr() as usize;
+        let offset = (at.as_ptr() as usize)
+            .checked_sub(start)
+            .filter(|&o| o <= src.len() && src.is_char_boundary(o))
+            .unwrap_or(src.len());
+        let before = &src[..offset];
+        let line = before.matches('\n').count() + 1;
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let column = before[line_start..].chars().count() + 1;
+        Self::new(line, column, at, expected)
+    }
+
+    /// Translate a nom error raised while parsing somewhere inside `src`.
+    pub fn from_nom(src: &str, err: nom::Err<nom::error::Error<&str>>, expected: &str) -> Self {
+        match err {
+            nom::Err::Error(e) | nom::Err::Failure(e) => Self::locate(src, e.input, expected),
+            nom::Err::Incomplete(_) => Self::locate(src, &src[src.len()..], expected),
+        }
+    }
+
+    /// Shift an error produced for a single line so that it points at line `line` of the input.
+    pub fn on_line(mut self, line: usize) -> Self {
+        self.line += line - 1;
+        self
+    }
+}
+
+impl Display for ParseError {
+    fn fmt(&self, f: &mut Formatter<'_>) -> FmtResult {
+        write!(
+            f,
+            "line {}, column {}: expected {}, ",
+            self.line, self.column, self.expected
+        )?;
+        if self.found.is_empty() {
+            write!(f, "found end of line")
+        } else {
+            write!(f, "found `{}`", self.found)
+        }
+    }
+}
+
+impl Error for ParseError {}
+
+/// Run a nom parser over a whole line of `src`, failing if any of the line is left over.
+pub fn parse_line<'a, O, P>(
+    src: &str,
+    line: &'a str,
+    parser: P,
+    expected: &str,
+) -> Result<O, ParseError>
+where
+    P: Parser<&'a str, O, nom::error::Error<&'a str>>,
+{
+    all_consuming(parser)(line)
+        .map(|(_, o)| o)
+        .map_err(|e| ParseError::from_nom(src, e, expected))
+}
+
+/// Check that `rows`, lines of `src` with a `cell` per char, make up a non-empty rectangle as wide
+/// as the first row.
+pub fn check_grid(src: &str, rows: &[&str], cell: &str) -> Result<(), ParseError> {
+    let Some(first) = rows.first() else {
+        let expected = format!("a grid of {}s", cell);
+        return Err(ParseError::locate(src, &src[src.len()..], &expected));
+    };
+    let width = first.chars().count();
+    for row in rows {
+        match row.char_indices().nth(width) {
+            Some((i, _)) => return Err(ParseError::locate(src, &row[i..], "the end of the row")),
+            None if row.chars().count() < width => {
+                let expected = format!("a row of {} {}s", width, cell);
+                return Err(ParseError::locate(src, &row[row.len()..], &expected));
+            }
+            None => {}
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn locate_subslice() {
+        let src = "abc\ndef ghi\n";
+        let at = &src[8..11];
+        let err = ParseError::locate(src, at, "a number");
+        assert_eq!(ParseError::new(2, 5, "ghi", "a number"), err);
+        assert_eq!(
+            "line 2, column 5: expected a number, found `ghi`",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn locate_end_of_input() {
+        let src = "abc";
+        let err = ParseError::locate(src, &src[3..], "more");
+        assert_eq!(ParseError::new(1, 4, "", "more"), err);
+        assert_eq!(
+            "line 1, column 4: expected more, found end of line",
+            err.to_string()
+        );
+    }
+
+    #[test]
+    fn ragged_grid() {
+        let src = "abc\nde\nfghi";
+        let rows = src.lines().collect::<Vec<&str>>();
+        assert_eq!(
+            Err(ParseError::new(2, 3, "", "a row of 3 cells")),
+            check_grid(src, &rows, "cell")
+        );
+        assert_eq!(
+            Err(ParseError::new(3, 4, "i", "the end of the row")),
+            check_grid(src, &[rows[0], rows[2]], "cell")
+        );
+        assert_eq!(
+            Err(ParseError::new(1, 1, "", "a grid of cells")),
+            check_grid("", &[], "cell")
+        );
+        assert_eq!(Ok(()), check_grid(src, &rows[..1], "cell"));
+    }
+}
@@ -6,6 +6,9 @@ extern crate aoc_runner;
 #[macro_use]
 extern crate aoc_runner_derive;
 
+pub mod error;
+pub mod solution;
+
 pub mod d01;
 pub mod d02;
 pub mod d03;
@@ -16,7 +19,9 @@ pub mod d07;
 pub mod d08;
 pub mod d09;
 pub mod d10;
-// pub mod d11;
-// pub mod d12;
+pub mod d11;
+pub mod d12;
+
+pub use solution::{solutions, Puzzle, Solution};
 
 aoc_lib! { year = 2022 }
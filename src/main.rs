@@ -0,0 +1,352 @@
+use std::error::Error;
+use std::io::Read;
+use std::panic::{self, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::Instant;
+
+use aoc_2022_rs::*;
+use aoc_runner::{ArcStr, Runner};
+
+const USAGE: &str = "\
+Usage: aoc-2022 --day N [--part P] [--variant NAME] [--input FILE]
+       aoc-2022 --all [--input-dir DIR]
+
+Options:
+    -d, --day N          day to run (1-25)
+    -p, --part P         part to run (1 or 2), both when omitted
+    -v, --variant NAME   alternate solver to use, e.g. `heap` or `iter`
+    -i, --input FILE     puzzle input, `-` or omitted for stdin
+    -a, --all            run every registered day, part and variant
+        --input-dir DIR  where `--all` finds `dayN.txt` inputs [default: input/2022]
+    -h, --help           print this message";
+
+type Generator = fn(ArcStr) -> Result<Box<dyn Runner>, Box<dyn Error>>;
+
+/// A solver registered through `#[aoc(dayN, partP[, name])]`.
+struct Variant {
+    day: u32,
+    part: u32,
+    name: Option<&'static str>,
+    generator: Generator,
+}
+
+macro_rules! variant {
+    ($day:literal, $part:literal, $gen:path) => {
+        Variant {
+            day: $day,
+            part: $part,
+            name: None,
+            generator: $gen,
+        }
+    };
+    ($day:literal, $part:literal, $name:ident, $gen:path) => {
+        Variant {
+            day: $day,
+            part: $part,
+            name: Some(stringify!($name)),
+            generator: $gen,
+        }
+    };
+}
+
+const VARIANTS: &[Variant] = &[
+    variant!(1, 1, iter, Factory::day1_part1_iter),
+    variant!(1, 1, heap, Factory::day1_part1_heap),
+    variant!(1, 2, iter, Factory::day1_part2_iter),
+    variant!(1, 2, heap, Factory::day1_part2_heap),
+    variant!(2, 1, Factory::day2_part1),
+    variant!(2, 2, Factory::day2_part2),
+    variant!(3, 1, Factory::day3_part1),
+    variant!(3, 2, Factory::day3_part2),
+    variant!(4, 1, Factory::day4_part1),
+    variant!(4, 2, Factory::day4_part2),
+    variant!(5, 1, Factory::day5_part1),
+    variant!(5, 2, Factory::day5_part2),
+    variant!(6, 1, Factory::day6_part1),
+    variant!(6, 2, Factory::day6_part2),
+    variant!(7, 1, Factory::day7_part1),
+    variant!(7, 2, Factory::day7_part2),
+    variant!(8, 1, Factory::day8_part1),
+    variant!(8, 2, Factory::day8_part2),
+    variant!(9, 1, Factory::day9_part1),
+    variant!(9, 2, Factory::day9_part2),
+    variant!(10, 1, Factory::day10_part1),
+    variant!(10, 2, Factory::day10_part2),
+    variant!(11, 1, Factory::day11_part1),
+    variant!(11, 2, Factory::day11_part2),
+    variant!(12, 1, Factory::day12_part1),
+    variant!(12, 2, Factory::day12_part2),
+];
+
+#[derive(Debug, Default, PartialEq, Eq)]
+struct Args {
+    day: Option<u32>,
+    part: Option<u32>,
+    variant: Option<String>,
+    input: Option<PathBuf>,
+    input_dir: Option<PathBuf>,
+    all: bool,
+    help: bool,
+}
+
+fn parse_args(args: impl IntoIterator<Item = String>) -> Result<Args, String> {
+    let mut parsed = Args::default();
+    let mut args = args.into_iter();
+
+    while let Some(arg) = args.next() {
+        let mut value = |name: &str| {
+            args.next()
+                .ok_or_else(|| format!("missing value for `{}`", name))
+        };
+        match arg.as_str() {
+            "-d" | "--day" => parsed.day = Some(number(&arg, &value(&arg)?)?),
+            "-p" | "--part" => parsed.part = Some(number(&arg, &value(&arg)?)?),
+            "-v" | "--variant" => parsed.variant = Some(value(&arg)?.to_lowercase()),
+            "-i" | "--input" => parsed.input = Some(value(&arg)?.into()),
+            "--input-dir" => parsed.input_dir = Some(value(&arg)?.into()),
+            "-a" | "--all" => parsed.all = true,
+            "-h" | "--help" => parsed.help = true,
+            _ => return Err(format!("unexpected argument `{}`", arg)),
+        }
+    }
+
+    if !parsed.help && parsed.all == parsed.day.is_some() {
+        return Err("expected exactly one of `--day` or `--all`".to_string());
+    }
+    if !matches!(parsed.part, None | Some(1 | 2)) {
+        return Err("`--part` must be 1 or 2".to_string());
+    }
+
+    Ok(parsed)
+}
+
+fn number(name: &str, value: &str) -> Result<u32, String> {
+    value
+        .parse()
+        .map_err(|_| format!("`{}` expects a number, got `{}`", name, value))
+}
+
+/// Pick the solvers to run for a day: the requested part(s), using the named variant if given and
+/// otherwise the first one registered for each part.
+fn select(
+    day: u32,
+    part: Option<u32>,
+    name: Option<&str>,
+) -> Result<Vec<&'static Variant>, String> {
+    let parts = match part {
+        Some(p) => vec![p],
+        None => vec![1, 2],
+    };
+
+    let selected = parts
+        .into_iter()
+        .filter_map(|p| {
+            let mut candidates = VARIANTS.iter().filter(|v| v.day == day && v.part == p);
+            match name {
+                Some(name) => candidates.find(|v| v.name == Some(name)),
+                None => candidates.next(),
+            }
+        })
+        .collect::<Vec<&Variant>>();
+
+    if selected.is_empty() {
+        let mut msg = format!("no solver registered for day {}", day);
+        if let Some(p) = part {
+            msg += &format!(", part {}", p);
+        }
+        if let Some(name) = name {
+            msg += &format!(", variant `{}`", name);
+        }
+        return Err(msg);
+    }
+
+    Ok(selected)
+}
+
+/// Generate and solve, printing the answer and how long each step took. Returns whether it
+/// succeeded.
+fn run(variant: &Variant, input: &ArcStr) -> bool {
+    let label = match variant.name {
+        Some(name) => format!("Day {} - Part {} - {}", variant.day, variant.part, name),
+        None => format!("Day {} - Part {}", variant.day, variant.part),
+    };
+
+    let outcome = panic::catch_unwind(AssertUnwindSafe(|| {
+        let start = Instant::now();
+        let runner = (variant.generator)(input.clone()).map_err(|e| ("generating", e))?;
+        let generated = Instant::now();
+        let answer = runner.try_run().map_err(|e| ("running", e))?;
+        let solved = Instant::now();
+        Ok((answer, generated - start, solved - generated))
+    }));
+
+    match outcome {
+        Ok(Ok((answer, gen_time, run_time))) => {
+            println!(
+                "{}: {}\n\tgenerator: {:?},\n\trunner: {:?}\n",
+                label, answer, gen_time, run_time
+            );
+            true
+        }
+        Ok(Err((step, e))) => {
+            eprintln!("{}: FAILED while {}:\n{}\n", label, step, e);
+            false
+        }
+        Err(_) => {
+            eprintln!("{}: FAILED, solver panicked\n", label);
+            false
+        }
+    }
+}
+
+fn read_input(path: Option<&Path>) -> Result<ArcStr, String> {
+    let input = match path.filter(|p| p.as_os_str() != "-") {
+        Some(p) => std::fs::read_to_string(p)
+            .map_err(|e| format!("could not read `{}`: {}", p.display(), e))?,
+        None => {
+            let mut buf = String::new();
+            std::io::stdin()
+                .read_to_string(&mut buf)
+                .map_err(|e| format!("could not read stdin: {}", e))?;
+            buf
+        }
+    };
+    Ok(ArcStr::from(&input))
+}
+
+fn run_all(input_dir: &Path) -> bool {
+    let mut days = VARIANTS.iter().map(|v| v.day).collect::<Vec<u32>>();
+    days.dedup();
+
+    let mut ok = true;
+    for day in days {
+        let path = input_dir.join(format!("day{}.txt", day));
+        let input = match read_input(Some(&path)) {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("Day {}: {}\n", day, e);
+                ok = false;
+                continue;
+            }
+        };
+        for variant in VARIANTS.iter().filter(|v| v.day == day) {
+            ok &= run(variant, &input);
+        }
+    }
+    ok
+}
+
+fn main() -> ExitCode {
+    let args = match parse_args(std::env::args().skip(1)) {
+        Ok(args) => args,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, USAGE);
+            return ExitCode::from(2);
+        }
+    };
+
+    if args.help {
+        println!("{}", USAGE);
+        return ExitCode::SUCCESS;
+    }
+
+    println!("Advent of code {}\n", YEAR);
+
+    let ok = if args.all {
+        run_all(
+            args.input_dir
+                .as_deref()
+                .unwrap_or_else(|| Path::new("input/2022")),
+        )
+    } else {
+        let day = args.day.unwrap();
+        let variants = match select(day, args.part, args.variant.as_deref()) {
+            Ok(variants) => variants,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return ExitCode::from(2);
+            }
+        };
+        let input = match read_input(args.input.as_deref()) {
+            Ok(input) => input,
+            Err(e) => {
+                eprintln!("error: {}", e);
+                return ExitCode::FAILURE;
+            }
+        };
+        // run every part, even after one fails
+        let mut ok = true;
+        for variant in variants {
+            ok &= run(variant, &input);
+        }
+        ok
+    };
+
+    if ok {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(s: &str) -> Result<Args, String> {
+        parse_args(s.split_whitespace().map(str::to_string))
+    }
+
+    #[test]
+    fn parse_day_part_variant() {
+        let expect = Args {
+            day: Some(1),
+            part: Some(2),
+            variant: Some("heap".to_string()),
+            input: Some("input.txt".into()),
+            ..Args::default()
+        };
+        assert_eq!(Ok(expect), args("--day 1 -p 2 --variant HEAP -i input.txt"));
+    }
+
+    #[test]
+    fn parse_rejects_bad_args() {
+        assert!(args("--part 1").is_err());
+        assert!(args("--all --day 1").is_err());
+        assert!(args("--day 1 --part 3").is_err());
+        assert!(args("--day one").is_err());
+        assert!(args("--day").is_err());
+        assert!(args("--all --bogus").is_err());
+    }
+
+    #[test]
+    fn select_variants() {
+        let names = |vs: Vec<&Variant>| {
+            vs.iter()
+                .map(|v| (v.part, v.name))
+                .collect::<Vec<(u32, Option<&str>)>>()
+        };
+        assert_eq!(
+            vec![(1, Some("iter")), (2, Some("iter"))],
+            names(select(1, None, None).unwrap())
+        );
+        assert_eq!(
+            vec![(2, Some("heap"))],
+            names(select(1, Some(2), Some("heap")).unwrap())
+        );
+        assert_eq!(vec![(1, None)], names(select(8, Some(1), None).unwrap()));
+        assert!(select(8, None, Some("heap")).is_err());
+        assert!(select(25, None, None).is_err());
+    }
+
+    #[test]
+    fn run_through_factory() {
+        let variant = select(8, Some(2), None).unwrap()[0];
+        assert!(run(
+            variant,
+            &ArcStr::from("30373\n25512\n65332\n33549\n35390")
+        ));
+        assert!(!run(variant, &ArcStr::from("303\n2x5")));
+    }
+}
@@ -0,0 +1,94 @@
+use std::error::Error;
+use std::fmt::Display;
+
+use crate::error::ParseError;
+use crate::{d01, d02, d03, d04, d05, d06, d07, d08, d09, d10, d11, d12};
+
+/// A day's puzzle: how to parse its input and solve each part, with the types each step produces.
+///
+/// Every day module has a unit struct (`d08::Day08`, ...) implementing this, so solvers can be
+/// called directly without going through aoc-runner.
+pub trait Puzzle {
+    /// The day of the advent calendar the puzzle belongs to.
+    const DAY: u32;
+
+    type Input;
+    type Output1: Display;
+    type Output2: Display;
+    /// Why solving a well-formed input can still fail, `Infallible` for most days.
+    type Error: Error + 'static;
+
+    fn parse(&self, input: &str) -> Result<Self::Input, ParseError>;
+    fn part1(&self, input: &Self::Input) -> Result<Self::Output1, Self::Error>;
+    fn part2(&self, input: &Self::Input) -> Result<Self::Output2, Self::Error>;
+}
+
+/// A type-erased [`Puzzle`] going straight from puzzle input to printable answers, so every day
+/// can live in one list.
+pub trait Solution: Sync {
+    fn day(&self) -> u32;
+    fn part1(&self, input: &str) -> Result<String, Box<dyn Error>>;
+    fn part2(&self, input: &str) -> Result<String, Box<dyn Error>>;
+}
+
+impl<P: Puzzle + Sync> Solution for P {
+    fn day(&self) -> u32 {
+        P::DAY
+    }
+
+    fn part1(&self, input: &str) -> Result<String, Box<dyn Error>> {
+        let input = self.parse(input)?;
+        Ok(Puzzle::part1(self, &input)?.to_string())
+    }
+
+    fn part2(&self, input: &str) -> Result<String, Box<dyn Error>> {
+        let input = self.parse(input)?;
+        Ok(Puzzle::part2(self, &input)?.to_string())
+    }
+}
+
+/// Every day's solution, ordered by day.
+pub fn solutions() -> &'static [&'static dyn Solution] {
+    &[
+        &d01::Day01,
+        &d02::Day02,
+        &d03::Day03,
+        &d04::Day04,
+        &d05::Day05,
+        &d06::Day06,
+        &d07::Day07,
+        &d08::Day08,
+        &d09::Day09,
+        &d10::Day10,
+        &d11::Day11,
+        &d12::Day12,
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn ordered_by_day() {
+        let days = solutions().iter().map(|s| s.day()).collect::<Vec<u32>>();
+        assert_eq!((1..=12).collect::<Vec<u32>>(), days);
+    }
+
+    #[test]
+    fn typed_and_erased_agree() {
+        let input = "30373\n25512\n65332\n33549\n35390";
+        let trees = d08::Day08.parse(input).unwrap();
+        assert_eq!(Ok(8), Puzzle::part2(&d08::Day08, &trees));
+        assert_eq!("8", Solution::part2(&d08::Day08, input).unwrap());
+    }
+
+    #[test]
+    fn erased_parse_error() {
+        let err = solutions()[7].part1("303\n2x5").unwrap_err();
+        assert_eq!(
+            Some(&ParseError::new(2, 2, "x5", "a tree height (0-9)")),
+            err.downcast_ref::<ParseError>()
+        );
+    }
+}